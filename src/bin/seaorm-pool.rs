@@ -0,0 +1,338 @@
+//! # seaorm-pool
+//!
+//! A command-line tool, built with the `cli` feature, for checking and
+//! exercising a configuration:
+//!
+//! - `check` loads and validates the configuration;
+//! - `print` shows the effective configuration, with secrets redacted;
+//! - `ping` connects to the database and reports the server and latency;
+//! - `bench` measures how long acquiring a connection takes under concurrency.
+//!
+//! The configuration is loaded with `ConfigLoader`, so environment files, the
+//! local override file and environment variables apply as they would in the
+//! application.
+
+use clap::{Parser, Subcommand, ValueEnum};
+use futures_util::future::try_join_all;
+use sea_orm::{ConnectionTrait, Statement};
+use seaorm_pool::{
+    AppConfig, ConfigLoader, ConfigSource, LoadedConfig, create_connection_pool, detect_server_info,
+};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
+use tracing::level_filters::LevelFilter;
+
+/// The value printed in place of a password.
+const REDACTED: &str = "***";
+
+/// Checks and exercises a seaorm-pool configuration.
+#[derive(Debug, Parser)]
+#[command(name = "seaorm-pool", version)]
+struct Cli {
+    /// The base configuration file, with or without extension.
+    #[arg(short, long, global = true, default_value = "Settings")]
+    config: PathBuf,
+
+    /// The environment whose file is layered on the base file, e.g. `production`.
+    #[arg(short, long, global = true)]
+    environment: Option<String>,
+
+    /// The prefix of the environment variables overriding file values.
+    #[arg(long, global = true, default_value = "APP")]
+    env_prefix: String,
+
+    /// Rejects unknown configuration fields instead of warning about them.
+    #[arg(long, global = true)]
+    strict: bool,
+
+    /// Logs pool activity; repeat for more detail.
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Loads and validates the configuration.
+    Check,
+    /// Prints the effective configuration, with secrets redacted.
+    Print {
+        /// The output format.
+        #[arg(short, long, value_enum, default_value_t = Format::Toml)]
+        format: Format,
+        /// Lists the file or environment variable that set each value instead.
+        #[arg(long)]
+        sources: bool,
+    },
+    /// Connects to the database and reports the server and query latency.
+    Ping {
+        /// The number of queries to time.
+        #[arg(short = 'n', long, default_value_t = 5)]
+        count: u32,
+    },
+    /// Measures connection acquire latency with concurrent tasks.
+    Bench {
+        /// The number of tasks acquiring connections concurrently.
+        #[arg(short = 'n', long, default_value_t = 10)]
+        connections: u32,
+        /// The number of connections each task acquires, one after another.
+        #[arg(short, long, default_value_t = 10)]
+        iterations: u32,
+    },
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Format {
+    Toml,
+    Yaml,
+    Json,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let level = match cli.verbose {
+        0 => LevelFilter::WARN,
+        1 => LevelFilter::INFO,
+        _ => LevelFilter::DEBUG,
+    };
+    tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_writer(std::io::stderr)
+        .init();
+
+    match run(cli).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(cli: Cli) -> Result<(), Box<dyn Error>> {
+    let mut loader = ConfigLoader::new(base_path(&cli.config))
+        .env_prefix(Some(&cli.env_prefix))
+        .strict(cli.strict);
+    if let Some(environment) = cli.environment {
+        loader = loader.environment(environment);
+    }
+    let loaded = loader.load()?;
+
+    match cli.command {
+        Command::Check => check(&loaded),
+        Command::Print { format, sources } => print(&loaded, format, sources),
+        Command::Ping { count } => ping(loaded.config, count).await,
+        Command::Bench {
+            connections,
+            iterations,
+        } => bench(loaded.config, connections, iterations).await,
+    }
+}
+
+/// Reports the files the valid configuration was loaded from.
+fn check(loaded: &LoadedConfig) -> Result<(), Box<dyn Error>> {
+    let mut files = Vec::new();
+    for (_, source) in loaded.sources() {
+        if let ConfigSource::File(path) = source
+            && !files.contains(&path)
+        {
+            files.push(path);
+        }
+    }
+    let files = files
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect::<Vec<_>>();
+    println!("Configuration is valid (loaded from {})", files.join(", "));
+    Ok(())
+}
+
+/// Prints the effective configuration, or the source of each value.
+fn print(loaded: &LoadedConfig, format: Format, sources: bool) -> Result<(), Box<dyn Error>> {
+    if sources {
+        for (path, source) in loaded.sources() {
+            println!("{path}: {source}");
+        }
+        return Ok(());
+    }
+    let config = redacted(&loaded.config);
+    let output = match format {
+        Format::Toml => toml::to_string_pretty(&config)?,
+        Format::Yaml => serde_yaml::to_string(&config)?,
+        Format::Json => serde_json::to_string_pretty(&config)? + "\n",
+    };
+    print!("{output}");
+    Ok(())
+}
+
+/// Connects to the database and times a few queries.
+async fn ping(config: AppConfig, count: u32) -> Result<(), Box<dyn Error>> {
+    let started = Instant::now();
+    let db = create_connection_pool(config.database).await?;
+    let info = detect_server_info(&db).await?;
+    println!(
+        "Connected to {info} (MySQL {}) in {}",
+        info.version,
+        format_duration(started.elapsed())
+    );
+
+    let mut latencies = Vec::new();
+    for _ in 0..count {
+        let started = Instant::now();
+        db.query_one(Statement::from_string(
+            db.get_database_backend(),
+            "SELECT 1",
+        ))
+        .await?;
+        latencies.push(started.elapsed());
+    }
+    if let Some(summary) = Summary::new(latencies) {
+        println!(
+            "{count} queries: min {}, avg {}, max {}",
+            format_duration(summary.min),
+            format_duration(summary.mean),
+            format_duration(summary.max)
+        );
+    }
+    db.close().await?;
+    Ok(())
+}
+
+/// Acquires connections from concurrent tasks and reports how long each
+/// acquisition took.
+async fn bench(config: AppConfig, connections: u32, iterations: u32) -> Result<(), Box<dyn Error>> {
+    let max_connections = config.database.pool_options.max_connections;
+    let db = create_connection_pool(config.database).await?;
+    let pool = db.get_mysql_connection_pool().clone();
+
+    let started = Instant::now();
+    let tasks = (0..connections).map(|_| {
+        let pool = pool.clone();
+        tokio::spawn(async move {
+            let mut latencies = Vec::new();
+            for _ in 0..iterations {
+                let acquire_started = Instant::now();
+                let mut connection = pool.acquire().await?;
+                latencies.push(acquire_started.elapsed());
+                sea_orm::sqlx::query("SELECT 1")
+                    .execute(&mut *connection)
+                    .await?;
+            }
+            Ok::<_, sea_orm::sqlx::Error>(latencies)
+        })
+    });
+    let mut latencies = Vec::new();
+    for result in try_join_all(tasks).await? {
+        latencies.extend(result?);
+    }
+    let elapsed = started.elapsed();
+    db.close().await?;
+
+    let acquisitions = latencies.len();
+    let Some(summary) = Summary::new(latencies) else {
+        println!("No connections acquired");
+        return Ok(());
+    };
+    println!(
+        "{acquisitions} acquisitions by {connections} tasks from a pool of at most \
+         {max_connections} connections in {}",
+        format_duration(elapsed)
+    );
+    println!(
+        "acquire latency: min {}, p50 {}, p95 {}, p99 {}, max {}",
+        format_duration(summary.min),
+        format_duration(summary.p50),
+        format_duration(summary.p95),
+        format_duration(summary.p99),
+        format_duration(summary.max)
+    );
+    Ok(())
+}
+
+/// Returns the configuration file path without a recognized extension, as
+/// expected by `ConfigLoader::new`.
+fn base_path(path: &Path) -> PathBuf {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("toml" | "yaml" | "yml" | "json") => path.with_extension(""),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Returns a copy of the configuration with its password redacted.
+fn redacted(config: &AppConfig) -> AppConfig {
+    let mut config = config.clone();
+    if !config.database.password.is_empty() {
+        config.database.password = REDACTED.to_string();
+    }
+    config
+}
+
+fn format_duration(duration: Duration) -> String {
+    format!("{:.2}ms", duration.as_secs_f64() * 1000.0)
+}
+
+/// Statistics of a set of latencies.
+#[derive(Debug, PartialEq)]
+struct Summary {
+    min: Duration,
+    mean: Duration,
+    p50: Duration,
+    p95: Duration,
+    p99: Duration,
+    max: Duration,
+}
+
+impl Summary {
+    /// Summarizes a set of latencies, or returns `None` if it is empty.
+    fn new(mut latencies: Vec<Duration>) -> Option<Self> {
+        if latencies.is_empty() {
+            return None;
+        }
+        latencies.sort();
+        let percentile = |p: usize| latencies[(latencies.len() * p).div_ceil(100).max(1) - 1];
+        Some(Self {
+            min: latencies[0],
+            mean: latencies.iter().sum::<Duration>() / latencies.len() as u32,
+            p50: percentile(50),
+            p95: percentile(95),
+            p99: percentile(99),
+            max: latencies[latencies.len() - 1],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test 1: Latency percentiles use the nearest-rank method.
+    #[test]
+    fn test_summary() {
+        let latencies = (1..=100).map(Duration::from_millis).collect();
+        let summary = Summary::new(latencies).unwrap();
+        assert_eq!(summary.min, Duration::from_millis(1));
+        assert_eq!(summary.p50, Duration::from_millis(50));
+        assert_eq!(summary.p95, Duration::from_millis(95));
+        assert_eq!(summary.max, Duration::from_millis(100));
+        assert_eq!(Summary::new(Vec::new()), None);
+    }
+
+    /// Test 2: Configuration paths are accepted with or without extension.
+    #[test]
+    fn test_base_path() {
+        assert_eq!(
+            base_path(Path::new("config/Settings.toml")),
+            Path::new("config/Settings")
+        );
+        assert_eq!(base_path(Path::new("Settings")), Path::new("Settings"));
+        assert_eq!(
+            base_path(Path::new("Settings.production")),
+            Path::new("Settings.production")
+        );
+    }
+}
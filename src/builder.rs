@@ -0,0 +1,379 @@
+//! # Configuration Builders
+//!
+//! This module provides fluent builders for `DatabaseConfig` and `PoolOptions`,
+//! for applications that build their configuration in code rather than
+//! loading it from a file. Fields that are not set keep their defaults, and
+//! `build()` runs the same validation as a loaded configuration.
+//!
+//! ```
+//! use seaorm_pool::DatabaseConfig;
+//! use std::time::Duration;
+//!
+//! let config = DatabaseConfig::builder()
+//!     .host("127.0.0.1")
+//!     .port(4000)
+//!     .username("app")
+//!     .password("secret")
+//!     .database_name("app_db")
+//!     .pool(|pool| pool.max_connections(20).idle_timeout(Duration::from_secs(60)))
+//!     .build()
+//!     .unwrap();
+//! assert_eq!(config.pool_options.max_connections, 20);
+//! ```
+
+use crate::config::{
+    CircuitBreakerOptions, DatabaseConfig, PoolOptions, PreflightOptions, RetryOptions, SslMode,
+    TableFamilyOptions, TidbTxnMode, TransactionOptions,
+};
+use crate::error::ConfigError;
+use crate::server_info::ServerVersion;
+use std::time::Duration;
+
+impl DatabaseConfig {
+    /// Returns a builder starting from the default configuration.
+    pub fn builder() -> DatabaseConfigBuilder {
+        DatabaseConfigBuilder::default()
+    }
+}
+
+impl PoolOptions {
+    /// Returns a builder starting from the default pool options.
+    pub fn builder() -> PoolOptionsBuilder {
+        PoolOptionsBuilder::default()
+    }
+}
+
+/// Builds a `DatabaseConfig`. See `DatabaseConfig::builder`.
+#[derive(Clone, Debug, Default)]
+pub struct DatabaseConfigBuilder {
+    config: DatabaseConfig,
+    host_set: bool,
+}
+
+impl DatabaseConfigBuilder {
+    /// Sets the hostname or IP address of the database server.
+    pub fn host(mut self, host: impl Into<String>) -> Self {
+        self.config.host = host.into();
+        self.host_set = true;
+        self
+    }
+
+    /// Sets the port of the database server.
+    pub fn port(mut self, port: u16) -> Self {
+        self.config.port = Some(port);
+        self
+    }
+
+    /// Connects through a Unix socket instead of TCP.
+    pub fn socket(mut self, path: impl Into<String>) -> Self {
+        self.config.socket = Some(path.into());
+        self
+    }
+
+    /// Sets the username used to authenticate.
+    pub fn username(mut self, username: impl Into<String>) -> Self {
+        self.config.username = username.into();
+        self
+    }
+
+    /// Sets the password used to authenticate.
+    pub fn password(mut self, password: impl Into<String>) -> Self {
+        self.config.password = password.into();
+        self
+    }
+
+    /// Sets the name of the database to connect to.
+    pub fn database_name(mut self, database_name: impl Into<String>) -> Self {
+        self.config.database_name = database_name.into();
+        self
+    }
+
+    /// Sets the path of the CA certificate used to verify the server.
+    pub fn ssl_ca(mut self, path: impl Into<String>) -> Self {
+        self.config.ssl_ca = Some(path.into());
+        self
+    }
+
+    /// Sets the SSL/TLS mode of the connection.
+    pub fn ssl_mode(mut self, mode: SslMode) -> Self {
+        self.config.ssl_mode = Some(mode);
+        self
+    }
+
+    /// Sets the character set of the connection.
+    pub fn charset(mut self, charset: impl Into<String>) -> Self {
+        self.config.charset = Some(charset.into());
+        self
+    }
+
+    /// Sets the collation of the connection.
+    pub fn collation(mut self, collation: impl Into<String>) -> Self {
+        self.config.collation = Some(collation.into());
+        self
+    }
+
+    /// Sets the session time zone (e.g. `+00:00`).
+    pub fn timezone(mut self, timezone: impl Into<String>) -> Self {
+        self.config.timezone = Some(timezone.into());
+        self
+    }
+
+    /// Adds a session variable applied on every newly opened connection.
+    pub fn session_variable(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.config
+            .session_variables
+            .insert(name.into(), value.into());
+        self
+    }
+
+    /// Adds a statement executed on every newly opened connection, after the
+    /// session variables.
+    pub fn init_statement(mut self, statement: impl Into<String>) -> Self {
+        self.config.init_statements.push(statement.into());
+        self
+    }
+
+    /// Sets the TiDB transaction mode of every session.
+    pub fn tidb_txn_mode(mut self, mode: TidbTxnMode) -> Self {
+        self.config.tidb_txn_mode = Some(mode);
+        self
+    }
+
+    /// Makes every read of the pool a TiDB stale read of data this old.
+    pub fn read_staleness(mut self, staleness: Duration) -> Self {
+        self.config.read_staleness = Some(staleness);
+        self
+    }
+
+    /// Binds every session to a TiDB resource group.
+    pub fn resource_group(mut self, group: impl Into<String>) -> Self {
+        self.config.resource_group = Some(group.into());
+        self
+    }
+
+    /// Declares a table family with its own settings.
+    pub fn table_family(mut self, name: impl Into<String>, options: TableFamilyOptions) -> Self {
+        self.config.table_families.insert(name.into(), options);
+        self
+    }
+
+    /// Sets the oldest server release the application supports.
+    pub fn min_server_version(mut self, version: ServerVersion) -> Self {
+        self.config.min_server_version = Some(version);
+        self
+    }
+
+    /// Sets the checks run right after the pool connects.
+    pub fn preflight(mut self, preflight: PreflightOptions) -> Self {
+        self.config.preflight = Some(preflight);
+        self
+    }
+
+    /// Sets the defaults applied to transactions run through a `PoolHandle`.
+    pub fn transaction(mut self, transaction: TransactionOptions) -> Self {
+        self.config.transaction = transaction;
+        self
+    }
+
+    /// Replaces the pool options.
+    pub fn pool_options(mut self, options: PoolOptions) -> Self {
+        self.config.pool_options = options;
+        self
+    }
+
+    /// Configures the pool options with a `PoolOptionsBuilder` starting from
+    /// the current ones. They are validated by `build()`.
+    pub fn pool(
+        mut self,
+        configure: impl FnOnce(PoolOptionsBuilder) -> PoolOptionsBuilder,
+    ) -> Self {
+        let builder = PoolOptionsBuilder {
+            options: self.config.pool_options,
+        };
+        self.config.pool_options = configure(builder).options;
+        self
+    }
+
+    /// Validates and returns the configuration.
+    ///
+    /// # Errors
+    /// Returns the `ConfigError` reported by `DatabaseConfig::validate`, or a
+    /// `ConfigError::Conflict` if both `host` and `socket` were set.
+    pub fn build(self) -> Result<DatabaseConfig, ConfigError> {
+        if self.host_set && self.config.socket.is_some() {
+            return Err(ConfigError::conflict("socket", "host"));
+        }
+        self.config.validate()?;
+        Ok(self.config)
+    }
+}
+
+/// Builds a `PoolOptions`. See `PoolOptions::builder` and
+/// `DatabaseConfigBuilder::pool`.
+#[derive(Clone, Debug, Default)]
+pub struct PoolOptionsBuilder {
+    options: PoolOptions,
+}
+
+impl PoolOptionsBuilder {
+    /// Sets the maximum number of connections.
+    pub fn max_connections(mut self, max_connections: u32) -> Self {
+        self.options.max_connections = max_connections;
+        self
+    }
+
+    /// Sets the minimum number of idle connections.
+    pub fn min_connections(mut self, min_connections: u32) -> Self {
+        self.options.min_connections = min_connections;
+        self
+    }
+
+    /// Sets how long to wait for a connection from the pool.
+    pub fn acquire_timeout(mut self, timeout: Duration) -> Self {
+        self.options.acquire_timeout = timeout;
+        self
+    }
+
+    /// Sets how long a single attempt to connect the pool may take.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.options.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets how long a connection may stay idle before it is closed.
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.options.idle_timeout = timeout;
+        self
+    }
+
+    /// Sets the maximum lifetime of a connection.
+    pub fn max_lifetime(mut self, lifetime: Duration) -> Self {
+        self.options.max_lifetime = lifetime;
+        self
+    }
+
+    /// Sets whether connections are only opened when first needed.
+    pub fn lazy(mut self, is_lazy: bool) -> Self {
+        self.options.is_lazy = is_lazy;
+        self
+    }
+
+    /// Sets the number of prepared statements cached per connection.
+    pub fn statement_cache_capacity(mut self, capacity: usize) -> Self {
+        self.options.statement_cache_capacity = capacity;
+        self
+    }
+
+    /// Sets whether idle connections are checked before being handed out.
+    pub fn test_before_acquire(mut self, test_before_acquire: bool) -> Self {
+        self.options.test_before_acquire = test_before_acquire;
+        self
+    }
+
+    /// Checks idle connections with `query` instead of a ping.
+    pub fn validation_query(mut self, query: impl Into<String>) -> Self {
+        self.options.validation_query = Some(query.into());
+        self
+    }
+
+    /// Only checks connections that have been idle for longer than `interval`.
+    pub fn validation_interval(mut self, interval: Duration) -> Self {
+        self.options.validation_interval = Some(interval);
+        self
+    }
+
+    /// Sets the retry policy for establishing the pool.
+    pub fn retry(mut self, retry: RetryOptions) -> Self {
+        self.options.retry = retry;
+        self
+    }
+
+    /// Enables the circuit breaker of `PoolHandle`.
+    pub fn circuit_breaker(mut self, circuit_breaker: CircuitBreakerOptions) -> Self {
+        self.options.circuit_breaker = Some(circuit_breaker);
+        self
+    }
+
+    /// Warns about connections held through a `PoolHandle` for longer than
+    /// `threshold`.
+    pub fn leak_detection_threshold(mut self, threshold: Duration) -> Self {
+        self.options.leak_detection_threshold = Some(threshold);
+        self
+    }
+
+    /// Validates and returns the pool options.
+    ///
+    /// # Errors
+    /// Returns a `ConfigError` for the first invalid option, with a field path
+    /// under `poolOptions` as in a configuration file.
+    pub fn build(self) -> Result<PoolOptions, ConfigError> {
+        self.options.validate("poolOptions")?;
+        Ok(self.options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test 1: Builders set fields and keep the defaults of the others.
+    #[test]
+    fn test_builders_set_fields() {
+        let config = DatabaseConfig::builder()
+            .host("db.internal")
+            .port(4000)
+            .username("app")
+            .session_variable("time_zone", "+00:00")
+            .init_statement("SET NAMES utf8mb4")
+            .pool(|pool| pool.max_connections(30).min_connections(5))
+            .pool(|pool| pool.lazy(true))
+            .build()
+            .unwrap();
+        assert_eq!(config.get_address(), "db.internal:4000");
+        assert_eq!(config.session_variables["time_zone"], "+00:00");
+        assert_eq!(config.init_statements, vec!["SET NAMES utf8mb4"]);
+        assert_eq!(config.pool_options.max_connections, 30);
+        assert_eq!(config.pool_options.min_connections, 5);
+        assert!(config.pool_options.is_lazy);
+        assert_eq!(
+            config.pool_options.idle_timeout,
+            PoolOptions::default().idle_timeout
+        );
+    }
+
+    /// Test 2: `build()` reports the same errors as configuration validation.
+    #[test]
+    fn test_build_validates() {
+        let err = DatabaseConfig::builder()
+            .socket("/tmp/mysql.sock")
+            .port(3306)
+            .build()
+            .unwrap_err();
+        assert_eq!(err, ConfigError::conflict("socket", "port"));
+
+        let err = DatabaseConfig::builder()
+            .host("localhost")
+            .socket("/tmp/mysql.sock")
+            .build()
+            .unwrap_err();
+        assert_eq!(err, ConfigError::conflict("socket", "host"));
+
+        let err = DatabaseConfig::builder()
+            .pool(|pool| pool.max_connections(2).min_connections(5))
+            .build()
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            ConfigError::InvalidValue { ref field, .. } if field == "poolOptions.minConnections"
+        ));
+
+        let err = PoolOptions::builder()
+            .max_connections(0)
+            .build()
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            ConfigError::InvalidValue { ref field, .. } if field == "poolOptions.maxConnections"
+        ));
+    }
+}
@@ -0,0 +1,381 @@
+//! # Circuit Breaker
+//!
+//! This module implements the circuit breaker placed in front of a `PoolHandle`
+//! when `PoolOptions::circuit_breaker` is configured.
+//!
+//! During a database brownout, every call would otherwise wait for the full
+//! `acquire_timeout` before failing. The breaker watches the outcome of recent
+//! calls and, once too many of them fail to reach the database, rejects new
+//! calls immediately with an error recognized by `is_circuit_open_error`.
+//!
+//! State transitions are logged through `tracing`, and counters are available
+//! through `CircuitBreaker::metrics`.
+//!
+//! The breaker lives in the `PoolHandle`, so it only guards pools created with
+//! `create_pool_handle`. A `DatabaseConnection` returned by
+//! `create_connection_pool` is a plain `sea-orm` connection without one.
+
+use crate::config::CircuitBreakerOptions;
+use sea_orm::{DbErr, RuntimeErr, sqlx};
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::Instant;
+use tracing::{info, warn};
+
+/// The message of the error returned while the circuit breaker is open.
+const CIRCUIT_OPEN_MESSAGE: &str = "circuit breaker is open: database calls are rejected";
+
+/// The state of a circuit breaker.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Calls are let through and their outcome is recorded.
+    Closed,
+    /// Calls are rejected immediately.
+    Open,
+    /// A limited number of probe calls are let through to test recovery.
+    HalfOpen,
+}
+
+impl fmt::Display for CircuitState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Closed => write!(f, "closed"),
+            Self::Open => write!(f, "open"),
+            Self::HalfOpen => write!(f, "half-open"),
+        }
+    }
+}
+
+/// A snapshot of the counters of a circuit breaker.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CircuitBreakerMetrics {
+    /// The current state of the breaker.
+    pub state: CircuitState,
+    /// The number of calls that reached the database.
+    pub successes: u64,
+    /// The number of calls that failed to reach the database.
+    pub failures: u64,
+    /// The number of calls rejected while the breaker was open.
+    pub rejected: u64,
+    /// The number of times the breaker has opened.
+    pub times_opened: u64,
+}
+
+/// A circuit breaker tracking the availability of the database.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    options: CircuitBreakerOptions,
+    state: Mutex<BreakerState>,
+}
+
+#[derive(Debug)]
+struct BreakerState {
+    state: CircuitState,
+    /// Outcomes of the most recent calls while closed, `true` meaning failure.
+    window: VecDeque<bool>,
+    opened_at: Option<Instant>,
+    probes_in_flight: u32,
+    probes_succeeded: u32,
+    metrics: CircuitBreakerMetrics,
+}
+
+/// Permission to perform one call, obtained from `CircuitBreaker::try_acquire`.
+///
+/// The outcome of the call must be reported with `Permit::record`. A permit
+/// dropped without recording an outcome (e.g. because the call was cancelled)
+/// is released without affecting the failure rate.
+#[derive(Debug)]
+pub struct Permit<'a> {
+    breaker: &'a CircuitBreaker,
+    probe: bool,
+    recorded: bool,
+}
+
+impl CircuitBreaker {
+    /// Creates a closed circuit breaker with the given options.
+    pub fn new(options: CircuitBreakerOptions) -> Self {
+        Self {
+            options,
+            state: Mutex::new(BreakerState {
+                state: CircuitState::Closed,
+                window: VecDeque::new(),
+                opened_at: None,
+                probes_in_flight: 0,
+                probes_succeeded: 0,
+                metrics: CircuitBreakerMetrics {
+                    state: CircuitState::Closed,
+                    successes: 0,
+                    failures: 0,
+                    rejected: 0,
+                    times_opened: 0,
+                },
+            }),
+        }
+    }
+
+    /// Returns the current state of the breaker.
+    pub fn state(&self) -> CircuitState {
+        self.metrics().state
+    }
+
+    /// Returns a snapshot of the breaker's counters.
+    pub fn metrics(&self) -> CircuitBreakerMetrics {
+        let mut inner = self.lock();
+        self.refresh(&mut inner);
+        inner.metrics.state = inner.state;
+        inner.metrics
+    }
+
+    /// Asks permission to perform a call.
+    ///
+    /// # Errors
+    /// Returns the circuit-open error if the breaker is open, or if it is
+    /// half-open and all probe slots are taken.
+    pub fn try_acquire(&self) -> Result<Permit<'_>, DbErr> {
+        let mut inner = self.lock();
+        self.refresh(&mut inner);
+        let probe = match inner.state {
+            CircuitState::Closed => false,
+            CircuitState::HalfOpen if inner.probes_in_flight < self.options.half_open_max_calls => {
+                inner.probes_in_flight += 1;
+                true
+            }
+            CircuitState::Open | CircuitState::HalfOpen => {
+                inner.metrics.rejected += 1;
+                return Err(circuit_open_error());
+            }
+        };
+        Ok(Permit {
+            breaker: self,
+            probe,
+            recorded: false,
+        })
+    }
+
+    /// Moves an open breaker to half-open once `open_duration` has elapsed.
+    fn refresh(&self, inner: &mut BreakerState) {
+        if inner.state == CircuitState::Open
+            && inner
+                .opened_at
+                .is_some_and(|opened_at| opened_at.elapsed() >= self.options.open_duration)
+        {
+            info!(circuit.state = %CircuitState::HalfOpen, "Circuit breaker half-open, probing the database");
+            inner.state = CircuitState::HalfOpen;
+            inner.probes_in_flight = 0;
+            inner.probes_succeeded = 0;
+        }
+    }
+
+    /// Records the outcome of a call made with a permit.
+    fn record(&self, probe: bool, failed: bool) {
+        let mut inner = self.lock();
+        if failed {
+            inner.metrics.failures += 1;
+        } else {
+            inner.metrics.successes += 1;
+        }
+
+        if probe {
+            inner.probes_in_flight = inner.probes_in_flight.saturating_sub(1);
+            if inner.state != CircuitState::HalfOpen {
+                return;
+            }
+            if failed {
+                warn!(circuit.state = %CircuitState::Open, "Circuit breaker probe failed, reopening");
+                self.open(&mut inner);
+            } else {
+                inner.probes_succeeded += 1;
+                if inner.probes_succeeded >= self.options.half_open_max_calls {
+                    info!(circuit.state = %CircuitState::Closed, "Circuit breaker closed, database recovered");
+                    inner.state = CircuitState::Closed;
+                    inner.window.clear();
+                }
+            }
+            return;
+        }
+
+        if inner.state != CircuitState::Closed {
+            return;
+        }
+        inner.window.push_back(failed);
+        while inner.window.len() > self.options.window_size as usize {
+            inner.window.pop_front();
+        }
+        if inner.window.len() >= self.options.minimum_calls as usize {
+            let failures = inner.window.iter().filter(|failed| **failed).count();
+            let rate = failures * 100 / inner.window.len();
+            if rate >= usize::from(self.options.failure_rate_threshold) {
+                warn!(
+                    circuit.state = %CircuitState::Open,
+                    circuit.failure_rate = rate,
+                    "Circuit breaker opened, rejecting database calls for {:?}",
+                    self.options.open_duration
+                );
+                self.open(&mut inner);
+            }
+        }
+    }
+
+    /// Opens the breaker.
+    fn open(&self, inner: &mut BreakerState) {
+        inner.state = CircuitState::Open;
+        inner.opened_at = Some(Instant::now());
+        inner.window.clear();
+        inner.metrics.times_opened += 1;
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BreakerState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Permit<'_> {
+    /// Records the outcome of the call made with this permit.
+    ///
+    /// Only errors showing that the database could not be reached count as
+    /// failures; errors such as constraint violations or syntax errors mean the
+    /// database is available.
+    pub fn record<T>(self, result: &Result<T, DbErr>) {
+        self.record_outcome(result.as_ref().is_err_and(is_availability_error));
+    }
+
+    /// Records whether the call made with this permit failed to reach the database.
+    pub(crate) fn record_outcome(mut self, failed: bool) {
+        self.recorded = true;
+        self.breaker.record(self.probe, failed);
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        if !self.recorded && self.probe {
+            let mut inner = self.breaker.lock();
+            inner.probes_in_flight = inner.probes_in_flight.saturating_sub(1);
+        }
+    }
+}
+
+/// Returns `true` if the error was returned by an open circuit breaker.
+pub fn is_circuit_open_error(err: &DbErr) -> bool {
+    matches!(err, DbErr::Conn(RuntimeErr::Internal(message)) if message == CIRCUIT_OPEN_MESSAGE)
+}
+
+/// Creates the error returned while the circuit breaker is open.
+fn circuit_open_error() -> DbErr {
+    DbErr::Conn(RuntimeErr::Internal(CIRCUIT_OPEN_MESSAGE.to_string()))
+}
+
+/// Checks whether an error shows that the database could not be reached.
+pub(crate) fn is_availability_error(err: &DbErr) -> bool {
+    match err {
+        DbErr::ConnectionAcquire(_) | DbErr::Conn(_) => true,
+        DbErr::Exec(RuntimeErr::SqlxError(err)) | DbErr::Query(RuntimeErr::SqlxError(err)) => {
+            matches!(
+                err,
+                sqlx::Error::Io(_)
+                    | sqlx::Error::Tls(_)
+                    | sqlx::Error::Protocol(_)
+                    | sqlx::Error::PoolTimedOut
+                    | sqlx::Error::PoolClosed
+                    | sqlx::Error::WorkerCrashed
+            )
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sea_orm::ConnAcquireErr;
+    use std::time::Duration;
+
+    fn breaker(open_duration: Duration) -> CircuitBreaker {
+        CircuitBreaker::new(CircuitBreakerOptions {
+            failure_rate_threshold: 50,
+            minimum_calls: 4,
+            window_size: 4,
+            open_duration,
+            half_open_max_calls: 2,
+        })
+    }
+
+    fn timeout() -> Result<(), DbErr> {
+        Err(DbErr::ConnectionAcquire(ConnAcquireErr::Timeout))
+    }
+
+    /// Test 1: The breaker opens once the failure rate reaches the threshold,
+    /// and rejects calls with a distinct error.
+    #[test]
+    fn test_opens_on_failure_rate() {
+        let breaker = breaker(Duration::from_secs(60));
+        breaker.try_acquire().unwrap().record(&Ok(()));
+        breaker.try_acquire().unwrap().record(&timeout());
+        breaker.try_acquire().unwrap().record(&Ok(()));
+        assert_eq!(breaker.state(), CircuitState::Closed);
+
+        breaker.try_acquire().unwrap().record(&timeout());
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        let err = breaker.try_acquire().unwrap_err();
+        assert!(is_circuit_open_error(&err));
+
+        let metrics = breaker.metrics();
+        assert_eq!(metrics.successes, 2);
+        assert_eq!(metrics.failures, 2);
+        assert_eq!(metrics.rejected, 1);
+        assert_eq!(metrics.times_opened, 1);
+    }
+
+    /// Test 2: Errors that do not concern availability do not open the breaker.
+    #[test]
+    fn test_query_errors_are_not_failures() {
+        let breaker = breaker(Duration::from_secs(60));
+        for _ in 0..10 {
+            breaker
+                .try_acquire()
+                .unwrap()
+                .record::<()>(&Err(DbErr::RecordNotFound("row".to_string())));
+        }
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    /// Test 3: After `open_duration`, probes are let through and close the
+    /// breaker when they all succeed, or reopen it when one fails.
+    #[test]
+    fn test_half_open_probes() {
+        let breaker = breaker(Duration::ZERO);
+        for _ in 0..4 {
+            breaker.try_acquire().unwrap().record(&timeout());
+        }
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+
+        let first = breaker.try_acquire().unwrap();
+        let second = breaker.try_acquire().unwrap();
+        assert!(is_circuit_open_error(&breaker.try_acquire().unwrap_err()));
+        first.record(&Ok(()));
+        second.record(&Ok(()));
+        assert_eq!(breaker.state(), CircuitState::Closed);
+
+        for _ in 0..4 {
+            breaker.try_acquire().unwrap().record(&timeout());
+        }
+        breaker.try_acquire().unwrap().record(&timeout());
+        assert_eq!(breaker.metrics().times_opened, 3);
+    }
+
+    /// Test 4: A dropped probe permit frees its slot.
+    #[test]
+    fn test_dropped_probe_releases_slot() {
+        let breaker = breaker(Duration::ZERO);
+        for _ in 0..4 {
+            breaker.try_acquire().unwrap().record(&timeout());
+        }
+        drop(breaker.try_acquire().unwrap());
+        drop(breaker.try_acquire().unwrap());
+        assert!(breaker.try_acquire().is_ok());
+    }
+}
@@ -0,0 +1,328 @@
+//! # Configuration Diffs
+//!
+//! This module compares two configurations field by field, for reviewing
+//! configuration changes and for applying them to a running pool.
+//!
+//! Fields are identified by their camelCase paths as written in configuration
+//! files (e.g. `poolOptions.maxConnections`). Passwords are redacted, so a diff
+//! can be logged or shown in a review as is.
+
+use crate::config::{AppConfig, DatabaseConfig};
+use serde_json::Value;
+use std::fmt;
+
+/// The value shown in place of a password.
+const REDACTED: &str = "***";
+
+/// Pool options applied by resizing the pool.
+const RESIZE_FIELDS: [&str; 4] = [
+    "poolOptions.maxConnections",
+    "poolOptions.minConnections",
+    "poolOptions.idleTimeout",
+    "poolOptions.maxLifetime",
+];
+
+/// Settings that are only read when a pool is built, by a resize or a rebuild.
+const NEXT_REBUILD_FIELDS: [&str; 8] = [
+    "poolOptions.retry",
+    "poolOptions.connectTimeout",
+    "poolOptions.testBeforeAcquire",
+    "poolOptions.validationQuery",
+    "poolOptions.validationInterval",
+    "preflight",
+    "minServerVersion",
+    "tableFamilies",
+];
+
+/// Settings of the `PoolHandle` itself, which are fixed when it is created.
+const RESTART_FIELDS: [&str; 3] = [
+    "poolOptions.circuitBreaker",
+    "poolOptions.leakDetectionThreshold",
+    "transaction",
+];
+
+/// How a configuration change can be applied to a running pool.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChangeImpact {
+    /// The pool can be resized: a new pool with the same connection settings
+    /// is built and swapped in, and the old one is closed once the calls in
+    /// flight on it have finished.
+    Resize,
+    /// New connections are required, so the pool must be rebuilt.
+    Reconnect,
+    /// The setting is only read when a pool is built, so the change takes
+    /// effect the next time the pool is resized or rebuilt from a
+    /// configuration that contains it.
+    NextRebuild,
+    /// The setting is fixed when the `PoolHandle` is created, so the change
+    /// requires a restart.
+    Restart,
+}
+
+impl ChangeImpact {
+    /// Classifies a change to a field of a `DatabaseConfig`.
+    fn of(path: &str) -> Self {
+        if matches_any(path, &RESIZE_FIELDS) {
+            Self::Resize
+        } else if matches_any(path, &NEXT_REBUILD_FIELDS) {
+            Self::NextRebuild
+        } else if matches_any(path, &RESTART_FIELDS) {
+            Self::Restart
+        } else {
+            Self::Reconnect
+        }
+    }
+}
+
+/// A field that differs between two configurations.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConfigChange {
+    /// The camelCase path of the field (e.g. `poolOptions.maxConnections`).
+    pub path: String,
+    /// The old value, or `Value::Null` if the field was not set.
+    pub old: Value,
+    /// The new value, or `Value::Null` if the field is no longer set.
+    pub new: Value,
+    /// How the change can be applied to a running pool.
+    pub impact: ChangeImpact,
+}
+
+impl ConfigChange {
+    /// Returns `true` if applying the change requires new connections.
+    pub fn requires_reconnect(&self) -> bool {
+        self.impact == ChangeImpact::Reconnect
+    }
+}
+
+impl fmt::Display for ConfigChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let show = |value: &Value| match value {
+            Value::Null => "(unset)".to_string(),
+            value => value.to_string(),
+        };
+        write!(
+            f,
+            "{}: {} -> {}",
+            self.path,
+            show(&self.old),
+            show(&self.new)
+        )
+    }
+}
+
+/// The differences between two configurations, as returned by
+/// `DatabaseConfig::diff` and `AppConfig::diff`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConfigDiff {
+    /// The changed fields, ordered by path.
+    pub changes: Vec<ConfigChange>,
+}
+
+impl ConfigDiff {
+    /// Returns `true` if the configurations are identical.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Returns `true` if any change requires new connections.
+    pub fn requires_reconnect(&self) -> bool {
+        self.changes.iter().any(ConfigChange::requires_reconnect)
+    }
+
+    /// Returns the changes with the given impact.
+    pub fn with_impact(&self, impact: ChangeImpact) -> impl Iterator<Item = &ConfigChange> {
+        self.changes
+            .iter()
+            .filter(move |change| change.impact == impact)
+    }
+
+    /// Prefixes every path with `prefix`, for a configuration nested in
+    /// another one.
+    fn nested_in(mut self, prefix: &str) -> Self {
+        for change in &mut self.changes {
+            change.path = format!("{prefix}.{}", change.path);
+        }
+        self
+    }
+}
+
+impl fmt::Display for ConfigDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, change) in self.changes.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{change}")?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> IntoIterator for &'a ConfigDiff {
+    type Item = &'a ConfigChange;
+    type IntoIter = std::slice::Iter<'a, ConfigChange>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.changes.iter()
+    }
+}
+
+impl AppConfig {
+    /// Compares this configuration with `other`, listing the fields that
+    /// changed from `self` to `other`.
+    ///
+    /// Paths are relative to the root of the configuration file (e.g.
+    /// `database.poolOptions.maxConnections`).
+    pub fn diff(&self, other: &AppConfig) -> ConfigDiff {
+        self.database.diff(&other.database).nested_in("database")
+    }
+}
+
+impl DatabaseConfig {
+    /// Compares this configuration with `other`, listing the fields that
+    /// changed from `self` to `other`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use seaorm_pool::{ChangeImpact, DatabaseConfig};
+    ///
+    /// let current = DatabaseConfig::default();
+    /// let mut new = current.clone();
+    /// new.pool_options.max_connections = 50;
+    ///
+    /// let diff = current.diff(&new);
+    /// assert_eq!(diff.changes[0].path, "poolOptions.maxConnections");
+    /// assert_eq!(diff.changes[0].impact, ChangeImpact::Resize);
+    /// assert!(!diff.requires_reconnect());
+    /// ```
+    pub fn diff(&self, other: &DatabaseConfig) -> ConfigDiff {
+        let mut changes = Vec::new();
+        collect_changes(
+            &serde_json::to_value(self).unwrap_or_default(),
+            &serde_json::to_value(other).unwrap_or_default(),
+            "",
+            &mut changes,
+        );
+        ConfigDiff { changes }
+    }
+}
+
+/// Returns `true` if `path` is one of `fields` or is nested in one of them.
+fn matches_any(path: &str, fields: &[&str]) -> bool {
+    fields.iter().any(|field| {
+        path == *field
+            || path
+                .strip_prefix(field)
+                .is_some_and(|rest| rest.starts_with('.'))
+    })
+}
+
+/// Collects the leaf values that differ between two serialized
+/// configurations.
+fn collect_changes(old: &Value, new: &Value, path: &str, changes: &mut Vec<ConfigChange>) {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            let mut keys = old.keys().chain(new.keys()).collect::<Vec<_>>();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                let path = match path {
+                    "" => key.clone(),
+                    _ => format!("{path}.{key}"),
+                };
+                collect_changes(
+                    old.get(key).unwrap_or(&Value::Null),
+                    new.get(key).unwrap_or(&Value::Null),
+                    &path,
+                    changes,
+                );
+            }
+        }
+        (old, new) if old != new => {
+            let redact = |value: &Value| match value {
+                Value::Null => Value::Null,
+                _ if path == "password" => Value::from(REDACTED),
+                value => value.clone(),
+            };
+            changes.push(ConfigChange {
+                path: path.to_string(),
+                old: redact(old),
+                new: redact(new),
+                impact: ChangeImpact::of(path),
+            });
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{CircuitBreakerOptions, SslMode};
+    use std::time::Duration;
+
+    /// Test 1: Changed fields are listed by path with their old and new values,
+    /// and passwords are redacted.
+    #[test]
+    fn test_diff_lists_changes() {
+        let current = AppConfig {
+            database: DatabaseConfig::default(),
+        };
+        let mut new = current.clone();
+        new.database.password = "rotated".to_string();
+        new.database.ssl_mode = Some(SslMode::Required);
+        new.database.pool_options.idle_timeout = Duration::from_secs(60);
+
+        let diff = current.diff(&new);
+        assert_eq!(
+            diff.to_string(),
+            "database.password: \"***\" -> \"***\"\n\
+             database.poolOptions.idleTimeout: \"5m\" -> \"1m\"\n\
+             database.sslMode: (unset) -> \"required\""
+        );
+        assert!(diff.requires_reconnect());
+        assert!(current.diff(&current).is_empty());
+    }
+
+    /// Test 2: Changes are classified by how they can be applied.
+    #[test]
+    fn test_diff_classifies_changes() {
+        let current = DatabaseConfig::default();
+        let mut new = current.clone();
+        new.pool_options.max_connections += 10;
+        new.pool_options.retry.max_attempts += 1;
+        new.pool_options.circuit_breaker = Some(CircuitBreakerOptions::default());
+        new.pool_options.validation_query = Some("SELECT 1".to_string());
+        new.host = "db.internal".to_string();
+
+        let impacts = current
+            .diff(&new)
+            .changes
+            .into_iter()
+            .map(|change| (change.path, change.impact))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            impacts,
+            vec![
+                ("host".to_string(), ChangeImpact::Reconnect),
+                (
+                    "poolOptions.circuitBreaker".to_string(),
+                    ChangeImpact::Restart
+                ),
+                (
+                    "poolOptions.maxConnections".to_string(),
+                    ChangeImpact::Resize
+                ),
+                (
+                    "poolOptions.retry.maxAttempts".to_string(),
+                    ChangeImpact::NextRebuild
+                ),
+                (
+                    "poolOptions.validationQuery".to_string(),
+                    ChangeImpact::NextRebuild
+                ),
+            ]
+        );
+    }
+}
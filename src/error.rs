@@ -0,0 +1,114 @@
+//! # Configuration Error Types
+//!
+//! This module defines the error returned when a configuration is rejected
+//! before any connection to the database is attempted, either because it cannot
+//! be parsed or because one of its values is invalid.
+//!
+//! Errors always carry the camelCase path of the offending field (for example
+//! `database.sessionVariables`), matching the keys used in configuration files,
+//! so that they can be reported to the user without further translation.
+
+use sea_orm::DbErr;
+use std::fmt;
+
+/// An error produced while validating a configuration.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConfigError {
+    /// A field holds a value that is not allowed.
+    InvalidValue {
+        /// The camelCase path of the field, e.g. `sessionVariables`.
+        field: String,
+        /// A human-readable explanation of why the value was rejected.
+        reason: String,
+    },
+
+    /// Two fields were set that cannot be used together.
+    Conflict {
+        /// The camelCase path of the field being validated.
+        field: String,
+        /// The camelCase path of the field it conflicts with.
+        other: String,
+    },
+
+    /// A field is not a known configuration field, usually because of a typo.
+    UnknownField {
+        /// The camelCase path of the field, e.g. `database.poolOptions.maxConnection`.
+        field: String,
+        /// The name of the known field closest to it, if any is close enough.
+        suggestion: Option<String>,
+    },
+
+    /// A configuration source could not be read or parsed.
+    Parse {
+        /// The source being parsed, usually a file path.
+        source: String,
+        /// A human-readable explanation of the failure.
+        reason: String,
+    },
+}
+
+impl ConfigError {
+    /// Creates a `ConfigError::InvalidValue` for the given field.
+    pub fn invalid_value(field: impl Into<String>, reason: impl Into<String>) -> Self {
+        Self::InvalidValue {
+            field: field.into(),
+            reason: reason.into(),
+        }
+    }
+
+    /// Creates a `ConfigError::Parse` for the given source.
+    pub fn parse(source: impl Into<String>, reason: impl Into<String>) -> Self {
+        Self::Parse {
+            source: source.into(),
+            reason: reason.into(),
+        }
+    }
+
+    /// Creates a `ConfigError::UnknownField` for the given field.
+    pub fn unknown_field(field: impl Into<String>, suggestion: Option<String>) -> Self {
+        Self::UnknownField {
+            field: field.into(),
+            suggestion,
+        }
+    }
+
+    /// Creates a `ConfigError::Conflict` between two fields.
+    pub fn conflict(field: impl Into<String>, other: impl Into<String>) -> Self {
+        Self::Conflict {
+            field: field.into(),
+            other: other.into(),
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidValue { field, reason } => {
+                write!(f, "invalid value for `{field}`: {reason}")
+            }
+            Self::Conflict { field, other } => {
+                write!(f, "`{field}` cannot be used together with `{other}`")
+            }
+            Self::UnknownField {
+                field,
+                suggestion: Some(suggestion),
+            } => write!(f, "unknown field `{field}`, did you mean `{suggestion}`?"),
+            Self::UnknownField {
+                field,
+                suggestion: None,
+            } => write!(f, "unknown field `{field}`"),
+            Self::Parse { source, reason } => write!(f, "failed to parse `{source}`: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Allows configuration errors to be propagated from functions returning `DbErr`,
+/// such as `create_connection_pool`.
+impl From<ConfigError> for DbErr {
+    fn from(err: ConfigError) -> Self {
+        DbErr::Custom(format!("invalid database configuration: {err}"))
+    }
+}
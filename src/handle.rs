@@ -0,0 +1,1152 @@
+//! # Pool Handle
+//!
+//! This module defines `PoolHandle`, a cloneable handle to a connection pool
+//! created by `create_pool_handle`.
+//!
+//! The handle implements `sea-orm`'s `ConnectionTrait`, `StreamTrait` and
+//! `TransactionTrait`, so it can be used anywhere a `DatabaseConnection` is
+//! expected. On top of the plain connection, it applies the features
+//! configured in `PoolOptions`, such as the circuit breaker and leak
+//! detection, and exposes the server detected when the pool was created.
+//!
+//! The handle also manages the pool's lifecycle: it can resize the pool at
+//! runtime by swapping in a rebuilt one, and shut it down gracefully.
+
+use crate::circuit_breaker::{CircuitBreaker, CircuitBreakerMetrics, is_availability_error};
+use crate::config::{DatabaseConfig, PoolOptions, TransactionOptions};
+use crate::leak_detection::{LeakDetector, LeakGuard};
+use crate::pool::connect_pool;
+use crate::server_info::ServerInfo;
+use crate::shutdown::ShutdownReport;
+use crate::transaction::run_in_transaction;
+use futures_util::Stream;
+use futures_util::future::join_all;
+use sea_orm::sqlx::MySqlPool;
+use sea_orm::{
+    AccessMode, ConnAcquireErr, ConnectionTrait, DatabaseBackend, DatabaseConnection,
+    DatabaseTransaction, DbErr, ExecResult, IsolationLevel, QueryResult, QueryStream, Statement,
+    StreamTrait, TransactionError, TransactionTrait,
+};
+use std::future::Future;
+use std::ops::Deref;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+/// A cloneable handle to a database connection pool.
+///
+/// Clones share the same pool and state. Use `PoolHandle::connection` to get
+/// the underlying `DatabaseConnection` when an API requires that exact type;
+/// calls made through it bypass the features applied by the handle.
+#[derive(Clone, Debug)]
+pub struct PoolHandle {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    pool: RwLock<PoolSlot>,
+    /// The configuration the current pool was built from, locked while the
+    /// pool is being resized.
+    config: tokio::sync::Mutex<DatabaseConfig>,
+    circuit_breaker: Option<CircuitBreaker>,
+    leak_detector: Option<Arc<LeakDetector>>,
+    transaction_options: TransactionOptions,
+    server_info: Option<ServerInfo>,
+    /// Pools replaced by a resize that may still be waiting to close, so that
+    /// `PoolHandle::shutdown` closes them as well.
+    draining: Mutex<Vec<MySqlPool>>,
+    closed: AtomicBool,
+}
+
+impl PoolHandle {
+    /// Wraps an established connection, applying the features configured in
+    /// `config.pool_options`.
+    pub fn new(connection: DatabaseConnection, config: &DatabaseConfig) -> Self {
+        Self::with_server_info(connection, config, None)
+    }
+
+    /// Wraps an established connection like `PoolHandle::new`, recording the
+    /// server it is connected to.
+    pub(crate) fn with_server_info(
+        connection: DatabaseConnection,
+        config: &DatabaseConfig,
+        server_info: Option<ServerInfo>,
+    ) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                pool: RwLock::new(PoolSlot::new(connection)),
+                config: tokio::sync::Mutex::new(config.clone()),
+                circuit_breaker: config
+                    .pool_options
+                    .circuit_breaker
+                    .clone()
+                    .map(CircuitBreaker::new),
+                leak_detector: config
+                    .pool_options
+                    .leak_detection_threshold
+                    .map(|threshold| Arc::new(LeakDetector::new(threshold))),
+                transaction_options: config.transaction,
+                server_info,
+                draining: Mutex::new(Vec::new()),
+                closed: AtomicBool::new(false),
+            }),
+        }
+    }
+
+    /// Returns the underlying `DatabaseConnection`.
+    ///
+    /// After `PoolHandle::resize`, this returns the new pool. The old pool is
+    /// closed once the calls made through the handle have finished, whether or
+    /// not a connection obtained here before is still held: calls made through
+    /// it then fail with a closed pool error.
+    pub fn connection(&self) -> Arc<DatabaseConnection> {
+        Arc::clone(&self.lease().connection)
+    }
+
+    /// Returns the current pool, marked as in use until the lease is dropped.
+    fn lease(&self) -> Lease {
+        self.inner
+            .pool
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .lease()
+    }
+
+    /// Returns the pool options of the current pool.
+    pub async fn pool_options(&self) -> PoolOptions {
+        self.inner.config.lock().await.pool_options.clone()
+    }
+
+    /// Returns the configuration of the current pool.
+    pub async fn config(&self) -> DatabaseConfig {
+        self.inner.config.lock().await.clone()
+    }
+
+    /// Changes the size and connection lifetimes of the pool at runtime.
+    ///
+    /// A new pool is built with the changed options, connecting to the
+    /// database as `create_connection_pool` does, then atomically swapped in:
+    /// calls made from then on use the new pool, while calls in flight finish
+    /// on the old one. The old pool is closed in the background once they are
+    /// done, or after its `acquire_timeout` if some are still running; closing
+    /// still waits for connections in use to be returned. Concurrent resizes
+    /// are applied one after the other.
+    ///
+    /// The new pool is built from the handle's current configuration, which
+    /// includes the settings applied by the last `reconfigure`, such as the
+    /// retry, connect timeout and validation settings.
+    ///
+    /// # Errors
+    /// Returns a `DbErr` if the pool has been shut down, if the changed options
+    /// are invalid, or if the new pool cannot connect. The current pool is kept
+    /// in that case.
+    pub async fn resize(&self, changes: PoolResize) -> Result<(), DbErr> {
+        self.ensure_open()?;
+        let mut config = self.inner.config.lock().await;
+        let mut resized = config.clone();
+        changes.apply(&mut resized.pool_options);
+        self.replace_pool(&resized, config.pool_options.acquire_timeout)
+            .await?;
+        info!(
+            "Database connection pool resized: {}",
+            PoolResize::describe(&config.pool_options, &resized.pool_options)
+        );
+        *config = resized;
+        Ok(())
+    }
+
+    /// Rebuilds the pool from a new configuration, for changes that require
+    /// new connections, such as a new host, credentials or TLS settings.
+    ///
+    /// The new pool is swapped in like in `PoolHandle::resize`, and every
+    /// setting of the pool comes from `new_config`: unlike `resize`, this also
+    /// applies the retry, connect timeout and validation settings, so it is
+    /// the way to resize the pool together with those changes. Features of
+    /// the handle itself (the circuit breaker, leak detection and transaction
+    /// defaults) keep the settings the handle was created with.
+    ///
+    /// # Errors
+    /// Returns a `DbErr` if the pool has been shut down, if the configuration
+    /// is invalid, or if the new pool cannot connect. The current pool is kept
+    /// in that case.
+    pub async fn reconfigure(&self, new_config: DatabaseConfig) -> Result<(), DbErr> {
+        self.ensure_open()?;
+        let mut config = self.inner.config.lock().await;
+        let mut rebuilt = new_config;
+        rebuilt.pool_options.circuit_breaker = config.pool_options.circuit_breaker.clone();
+        rebuilt.pool_options.leak_detection_threshold =
+            config.pool_options.leak_detection_threshold;
+        rebuilt.transaction = config.transaction;
+        self.replace_pool(&rebuilt, config.pool_options.acquire_timeout)
+            .await?;
+        if config.diff(&rebuilt).requires_reconnect() {
+            info!(
+                "Database connection pool rebuilt for '{}'.",
+                rebuilt.get_address()
+            );
+        } else {
+            info!(
+                "Database connection pool resized: {}",
+                PoolResize::describe(&config.pool_options, &rebuilt.pool_options)
+            );
+        }
+        *config = rebuilt;
+        Ok(())
+    }
+
+    /// Builds a pool from `config` and swaps it in, closing the replaced pool
+    /// once it is no longer in use, or after `drain_limit`.
+    ///
+    /// If the handle is shut down while the new pool connects, the new pool is
+    /// closed instead and the current one is kept for the shutdown to close.
+    async fn replace_pool(
+        &self,
+        config: &DatabaseConfig,
+        drain_limit: Duration,
+    ) -> Result<(), DbErr> {
+        let (connection, _) = connect_pool(config.clone()).await?;
+        let old = {
+            let mut current = self
+                .inner
+                .pool
+                .write()
+                .unwrap_or_else(PoisonError::into_inner);
+            // `shutdown` marks the handle closed before reading the current
+            // pool, so checking under the write lock means it either sees the
+            // new pool or this sees the handle closed.
+            if self.is_closed() {
+                Err(connection)
+            } else {
+                Ok(std::mem::replace(&mut *current, PoolSlot::new(connection)))
+            }
+        };
+        let old = match old {
+            Ok(old) => old,
+            Err(connection) => {
+                close_pool(&connection).await;
+                return Err(DbErr::ConnectionAcquire(ConnAcquireErr::ConnectionClosed));
+            }
+        };
+        if let DatabaseConnection::SqlxMySqlPoolConnection(_) = &*old.connection {
+            let mut draining = self
+                .inner
+                .draining
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            draining.retain(|pool| !pool.is_closed());
+            draining.push(old.connection.get_mysql_connection_pool().clone());
+        }
+        tokio::spawn(old.close_when_unused(drain_limit));
+        Ok(())
+    }
+
+    /// Returns the number of connections held for longer than
+    /// `PoolOptions::leak_detection_threshold` so far, or `None` if leak
+    /// detection is disabled.
+    pub fn leaked_connections(&self) -> Option<u64> {
+        self.inner
+            .leak_detector
+            .as_ref()
+            .map(|detector| detector.detected())
+    }
+
+    /// Returns the flavor and version of the server, or `None` if it was not
+    /// detected because the pool is lazy or was not created by
+    /// `create_pool_handle`.
+    pub fn server_info(&self) -> Option<&ServerInfo> {
+        self.inner.server_info.as_ref()
+    }
+
+    /// Returns a snapshot of the circuit breaker's state and counters, or
+    /// `None` if no circuit breaker is configured.
+    pub fn circuit_breaker_metrics(&self) -> Option<CircuitBreakerMetrics> {
+        self.inner
+            .circuit_breaker
+            .as_ref()
+            .map(CircuitBreaker::metrics)
+    }
+
+    /// Returns the transaction defaults configured in
+    /// `DatabaseConfig::transaction`.
+    pub fn transaction_options(&self) -> TransactionOptions {
+        self.inner.transaction_options
+    }
+
+    /// Runs `callback` in a transaction using the pool's transaction defaults.
+    ///
+    /// See `run_in_transaction` for how the transaction is committed or rolled
+    /// back. To override a default for one transaction, call
+    /// `run_in_transaction` with options derived from
+    /// `PoolHandle::transaction_options`.
+    pub async fn run_in_transaction<F, T, E>(&self, callback: F) -> Result<T, E>
+    where
+        F: for<'c> FnOnce(
+                &'c DatabaseTransaction,
+            ) -> Pin<Box<dyn Future<Output = Result<T, E>> + Send + 'c>>
+            + Send,
+        T: Send,
+        E: From<DbErr> + Send,
+    {
+        self.tracked(run_in_transaction(
+            self,
+            &self.inner.transaction_options,
+            callback,
+        ))
+        .await
+    }
+
+    /// Starts a transaction that the leak detector tracks until it is
+    /// committed, rolled back or dropped.
+    ///
+    /// Use this instead of `TransactionTrait::begin` when leak detection is
+    /// enabled: a plain `DatabaseTransaction` is only tracked until it starts.
+    ///
+    /// # Errors
+    /// Returns a `DbErr` if the transaction cannot be started.
+    pub async fn begin_tracked(&self) -> Result<PoolTransaction, DbErr> {
+        self.begin_tracked_with_config(None, None).await
+    }
+
+    /// Starts a tracked transaction like `PoolHandle::begin_tracked`, with the
+    /// given isolation level and access mode.
+    ///
+    /// # Errors
+    /// Returns a `DbErr` if the transaction cannot be started.
+    pub async fn begin_tracked_with_config(
+        &self,
+        isolation_level: Option<IsolationLevel>,
+        access_mode: Option<AccessMode>,
+    ) -> Result<PoolTransaction, DbErr> {
+        let leak_guard = self.inner.leak_detector.as_ref().map(LeakDetector::guard);
+        let txn = self.begin_with_config(isolation_level, access_mode).await?;
+        Ok(PoolTransaction {
+            txn,
+            _leak_guard: leak_guard,
+        })
+    }
+
+    /// Shuts the pool down, waiting up to `deadline` for connections in use to
+    /// be returned.
+    ///
+    /// New calls through the handle, and through its clones, fail immediately
+    /// with `ConnAcquireErr::ConnectionClosed`. Idle connections are closed
+    /// right away, and the others as they are returned. Connections still in
+    /// use at the deadline are counted in `ShutdownReport::force_closed` and
+    /// closed as soon as they are released. Pools replaced by a resize that
+    /// are still draining are closed the same way, and a resize in progress
+    /// fails.
+    pub async fn shutdown(&self, deadline: Duration) -> ShutdownReport {
+        let started = Instant::now();
+        if self.inner.closed.swap(true, Ordering::AcqRel) {
+            return ShutdownReport::default();
+        }
+        info!("Shutting down the database connection pool...");
+
+        let connection = self.connection();
+        let mut pools = std::mem::take(
+            &mut *self
+                .inner
+                .draining
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner),
+        );
+        if let DatabaseConnection::SqlxMySqlPoolConnection(_) = &*connection {
+            pools.push(connection.get_mysql_connection_pool().clone());
+        }
+        let in_use = pools
+            .iter()
+            .map(|pool| pool.size().saturating_sub(pool.num_idle() as u32))
+            .sum();
+        let close_all = join_all(pools.iter().map(MySqlPool::close));
+        let force_closed = match tokio::time::timeout(deadline, close_all).await {
+            Ok(_) => 0,
+            Err(_) => pools.iter().map(MySqlPool::size).sum(),
+        };
+        let report = ShutdownReport {
+            in_use,
+            force_closed,
+            elapsed: started.elapsed(),
+        };
+
+        if report.is_clean() {
+            info!("Database connection pool closed in {:?}.", report.elapsed);
+        } else {
+            warn!(
+                "Database connection pool closed after {:?} with {} connection(s) still in use.",
+                report.elapsed, report.force_closed
+            );
+        }
+        report
+    }
+
+    /// Waits for `signal`, then shuts the pool down like `PoolHandle::shutdown`.
+    ///
+    /// Combined with `shutdown_signal`, this closes the pool when the process is
+    /// asked to terminate.
+    pub async fn shutdown_on<S>(self, signal: S, deadline: Duration) -> ShutdownReport
+    where
+        S: Future<Output = ()>,
+    {
+        signal.await;
+        self.shutdown(deadline).await
+    }
+
+    /// Returns `true` once `PoolHandle::shutdown` has been called.
+    pub fn is_closed(&self) -> bool {
+        self.inner.closed.load(Ordering::Acquire)
+    }
+
+    /// Fails if the pool has been shut down.
+    fn ensure_open(&self) -> Result<(), DbErr> {
+        if self.is_closed() {
+            return Err(DbErr::ConnectionAcquire(ConnAcquireErr::ConnectionClosed));
+        }
+        Ok(())
+    }
+
+    /// Runs a future holding a connection through the leak detector, if one is
+    /// configured.
+    async fn tracked<F: Future>(&self, call: F) -> F::Output {
+        match &self.inner.leak_detector {
+            Some(detector) => detector.track(call).await,
+            None => call.await,
+        }
+    }
+
+    /// Runs a database call through the circuit breaker and the leak
+    /// detector, if they are configured.
+    async fn guarded<T, F>(&self, call: F) -> Result<T, DbErr>
+    where
+        F: Future<Output = Result<T, DbErr>>,
+    {
+        self.guarded_untracked(self.tracked(call)).await
+    }
+
+    /// Runs a database call through the circuit breaker, if one is configured,
+    /// for calls whose connection the leak detector tracks otherwise.
+    async fn guarded_untracked<T, F>(&self, call: F) -> Result<T, DbErr>
+    where
+        F: Future<Output = Result<T, DbErr>>,
+    {
+        self.ensure_open()?;
+        match &self.inner.circuit_breaker {
+            Some(breaker) => {
+                let permit = breaker.try_acquire()?;
+                let result = call.await;
+                permit.record(&result);
+                result
+            }
+            None => call.await,
+        }
+    }
+
+    /// Runs a transaction callback through the circuit breaker, if one is
+    /// configured. Only connection errors count as failures.
+    async fn guarded_transaction<T, E, F>(&self, call: F) -> Result<T, TransactionError<E>>
+    where
+        F: Future<Output = Result<T, TransactionError<E>>>,
+    {
+        self.ensure_open().map_err(TransactionError::Connection)?;
+        match &self.inner.circuit_breaker {
+            Some(breaker) => {
+                let permit = breaker
+                    .try_acquire()
+                    .map_err(TransactionError::Connection)?;
+                let result = self.tracked(call).await;
+                permit.record_outcome(matches!(
+                    &result,
+                    Err(TransactionError::Connection(err)) if is_availability_error(err)
+                ));
+                result
+            }
+            None => self.tracked(call).await,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl ConnectionTrait for PoolHandle {
+    fn get_database_backend(&self) -> DatabaseBackend {
+        self.connection().get_database_backend()
+    }
+
+    async fn execute(&self, stmt: Statement) -> Result<ExecResult, DbErr> {
+        let lease = self.lease();
+        self.guarded(lease.connection.execute(stmt)).await
+    }
+
+    async fn execute_unprepared(&self, sql: &str) -> Result<ExecResult, DbErr> {
+        let lease = self.lease();
+        self.guarded(lease.connection.execute_unprepared(sql)).await
+    }
+
+    async fn query_one(&self, stmt: Statement) -> Result<Option<QueryResult>, DbErr> {
+        let lease = self.lease();
+        self.guarded(lease.connection.query_one(stmt)).await
+    }
+
+    async fn query_all(&self, stmt: Statement) -> Result<Vec<QueryResult>, DbErr> {
+        let lease = self.lease();
+        self.guarded(lease.connection.query_all(stmt)).await
+    }
+
+    fn support_returning(&self) -> bool {
+        self.connection().support_returning()
+    }
+
+    fn is_mock_connection(&self) -> bool {
+        self.connection().is_mock_connection()
+    }
+}
+
+/// Changes applied to a pool by `PoolHandle::resize`. Options left as `None`
+/// keep their current value.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PoolResize {
+    /// The new maximum number of connections.
+    pub max_connections: Option<u32>,
+    /// The new minimum number of idle connections.
+    pub min_connections: Option<u32>,
+    /// The new maximum idle time of a connection.
+    pub idle_timeout: Option<Duration>,
+    /// The new maximum lifetime of a connection.
+    pub max_lifetime: Option<Duration>,
+}
+
+impl PoolResize {
+    /// Applies the changes to a set of pool options.
+    fn apply(&self, options: &mut PoolOptions) {
+        if let Some(max_connections) = self.max_connections {
+            options.max_connections = max_connections;
+        }
+        if let Some(min_connections) = self.min_connections {
+            options.min_connections = min_connections;
+        }
+        if let Some(idle_timeout) = self.idle_timeout {
+            options.idle_timeout = idle_timeout;
+        }
+        if let Some(max_lifetime) = self.max_lifetime {
+            options.max_lifetime = max_lifetime;
+        }
+    }
+
+    /// Describes the options changed between `before` and `after`, for logging.
+    fn describe(before: &PoolOptions, after: &PoolOptions) -> String {
+        let mut changes = Vec::new();
+        if before.max_connections != after.max_connections {
+            changes.push(format!(
+                "max connections {} -> {}",
+                before.max_connections, after.max_connections
+            ));
+        }
+        if before.min_connections != after.min_connections {
+            changes.push(format!(
+                "min connections {} -> {}",
+                before.min_connections, after.min_connections
+            ));
+        }
+        if before.idle_timeout != after.idle_timeout {
+            changes.push(format!(
+                "idle timeout {:?} -> {:?}",
+                before.idle_timeout, after.idle_timeout
+            ));
+        }
+        if before.max_lifetime != after.max_lifetime {
+            changes.push(format!(
+                "max lifetime {:?} -> {:?}",
+                before.max_lifetime, after.max_lifetime
+            ));
+        }
+        if changes.is_empty() {
+            "no changes".to_string()
+        } else {
+            changes.join(", ")
+        }
+    }
+}
+
+/// The pool currently used by a `PoolHandle`.
+#[derive(Debug)]
+struct PoolSlot {
+    connection: Arc<DatabaseConnection>,
+    /// Cloned into every lease; the channel closes once the slot and all its
+    /// leases have been dropped.
+    in_use: mpsc::Sender<()>,
+    released: mpsc::Receiver<()>,
+}
+
+/// A pool used by a call made through a `PoolHandle`, keeping the pool open
+/// after a resize until the call has finished.
+#[derive(Debug)]
+struct Lease {
+    connection: Arc<DatabaseConnection>,
+    _in_use: mpsc::Sender<()>,
+}
+
+impl PoolSlot {
+    fn new(connection: DatabaseConnection) -> Self {
+        let (in_use, released) = mpsc::channel(1);
+        Self {
+            connection: Arc::new(connection),
+            in_use,
+            released,
+        }
+    }
+
+    fn lease(&self) -> Lease {
+        Lease {
+            connection: Arc::clone(&self.connection),
+            _in_use: self.in_use.clone(),
+        }
+    }
+
+    /// Closes a pool replaced by `PoolHandle::resize` once the calls that were
+    /// using it have finished, or after `limit` if some are still running.
+    async fn close_when_unused(self, limit: Duration) {
+        let Self {
+            connection,
+            in_use,
+            mut released,
+        } = self;
+        drop(in_use);
+        // Nothing is ever sent: `recv` returns once every lease is dropped.
+        if tokio::time::timeout(limit, released.recv()).await.is_err() {
+            warn!(
+                "Closing the replaced database connection pool with calls still in flight after {:?}.",
+                limit
+            );
+        }
+        close_pool(&connection).await;
+    }
+}
+
+/// Closes the `sqlx` pool behind a connection, waiting for the connections in
+/// use to be returned.
+async fn close_pool(connection: &DatabaseConnection) {
+    if let DatabaseConnection::SqlxMySqlPoolConnection(_) = connection {
+        connection.get_mysql_connection_pool().close().await;
+    }
+}
+
+/// A stream of query results returned by `PoolHandle::stream`.
+///
+/// The stream holds a connection until it is dropped, which the leak detector
+/// tracks when it is enabled.
+pub struct PoolStream {
+    inner: QueryStream,
+    _leak_guard: Option<LeakGuard>,
+}
+
+impl Stream for PoolStream {
+    type Item = Result<QueryResult, DbErr>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.get_mut().inner).poll_next(cx)
+    }
+}
+
+impl StreamTrait for PoolHandle {
+    type Stream<'a> = PoolStream;
+
+    fn stream<'a>(
+        &'a self,
+        stmt: Statement,
+    ) -> Pin<Box<dyn Future<Output = Result<Self::Stream<'a>, DbErr>> + 'a + Send>> {
+        Box::pin(async move {
+            // The guard tracks the connection from the call opening the stream
+            // until the stream is dropped, so the call itself is not tracked.
+            let leak_guard = self.inner.leak_detector.as_ref().map(LeakDetector::guard);
+            let lease = self.lease();
+            let inner = self
+                .guarded_untracked(lease.connection.stream(stmt))
+                .await?;
+            Ok(PoolStream {
+                inner,
+                _leak_guard: leak_guard,
+            })
+        })
+    }
+}
+
+/// A transaction started by `PoolHandle::begin_tracked`.
+///
+/// It dereferences to the underlying `DatabaseTransaction` and can be passed
+/// wherever a `ConnectionTrait` is expected. The leak detector, when enabled,
+/// tracks the transaction until it is committed, rolled back or dropped.
+#[derive(Debug)]
+pub struct PoolTransaction {
+    txn: DatabaseTransaction,
+    _leak_guard: Option<LeakGuard>,
+}
+
+impl PoolTransaction {
+    /// Commits the transaction.
+    ///
+    /// # Errors
+    /// Returns a `DbErr` if the commit fails.
+    pub async fn commit(self) -> Result<(), DbErr> {
+        self.txn.commit().await
+    }
+
+    /// Rolls the transaction back.
+    ///
+    /// # Errors
+    /// Returns a `DbErr` if the rollback fails.
+    pub async fn rollback(self) -> Result<(), DbErr> {
+        self.txn.rollback().await
+    }
+}
+
+impl Deref for PoolTransaction {
+    type Target = DatabaseTransaction;
+
+    fn deref(&self) -> &Self::Target {
+        &self.txn
+    }
+}
+
+#[async_trait::async_trait]
+impl ConnectionTrait for PoolTransaction {
+    fn get_database_backend(&self) -> DatabaseBackend {
+        self.txn.get_database_backend()
+    }
+
+    async fn execute(&self, stmt: Statement) -> Result<ExecResult, DbErr> {
+        self.txn.execute(stmt).await
+    }
+
+    async fn execute_unprepared(&self, sql: &str) -> Result<ExecResult, DbErr> {
+        self.txn.execute_unprepared(sql).await
+    }
+
+    async fn query_one(&self, stmt: Statement) -> Result<Option<QueryResult>, DbErr> {
+        self.txn.query_one(stmt).await
+    }
+
+    async fn query_all(&self, stmt: Statement) -> Result<Vec<QueryResult>, DbErr> {
+        self.txn.query_all(stmt).await
+    }
+
+    fn support_returning(&self) -> bool {
+        self.txn.support_returning()
+    }
+
+    fn is_mock_connection(&self) -> bool {
+        self.txn.is_mock_connection()
+    }
+}
+
+#[async_trait::async_trait]
+impl TransactionTrait for PoolHandle {
+    /// Starts a transaction.
+    ///
+    /// The leak detector only tracks the transaction until it has started; use
+    /// `PoolHandle::begin_tracked` to track it until it ends.
+    async fn begin(&self) -> Result<DatabaseTransaction, DbErr> {
+        let lease = self.lease();
+        self.guarded(lease.connection.begin()).await
+    }
+
+    async fn begin_with_config(
+        &self,
+        isolation_level: Option<IsolationLevel>,
+        access_mode: Option<AccessMode>,
+    ) -> Result<DatabaseTransaction, DbErr> {
+        let lease = self.lease();
+        self.guarded(
+            lease
+                .connection
+                .begin_with_config(isolation_level, access_mode),
+        )
+        .await
+    }
+
+    async fn transaction<F, T, E>(&self, callback: F) -> Result<T, TransactionError<E>>
+    where
+        F: for<'c> FnOnce(
+                &'c DatabaseTransaction,
+            ) -> Pin<Box<dyn Future<Output = Result<T, E>> + Send + 'c>>
+            + Send,
+        T: Send,
+        E: std::fmt::Display + std::fmt::Debug + Send,
+    {
+        let lease = self.lease();
+        self.guarded_transaction(lease.connection.transaction(callback))
+            .await
+    }
+
+    async fn transaction_with_config<F, T, E>(
+        &self,
+        callback: F,
+        isolation_level: Option<IsolationLevel>,
+        access_mode: Option<AccessMode>,
+    ) -> Result<T, TransactionError<E>>
+    where
+        F: for<'c> FnOnce(
+                &'c DatabaseTransaction,
+            ) -> Pin<Box<dyn Future<Output = Result<T, E>> + Send + 'c>>
+            + Send,
+        T: Send,
+        E: std::fmt::Display + std::fmt::Debug + Send,
+    {
+        let lease = self.lease();
+        self.guarded_transaction(lease.connection.transaction_with_config(
+            callback,
+            isolation_level,
+            access_mode,
+        ))
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit_breaker::{CircuitState, is_circuit_open_error};
+    use crate::config::{CircuitBreakerOptions, TransactionIsolation};
+    use sea_orm::{MockDatabase, MockExecResult, RuntimeErr};
+    use std::time::Duration;
+
+    fn handle_with_breaker(connection: DatabaseConnection) -> PoolHandle {
+        let mut config = DatabaseConfig::default();
+        config.pool_options.circuit_breaker = Some(CircuitBreakerOptions {
+            minimum_calls: 2,
+            window_size: 2,
+            open_duration: Duration::from_secs(60),
+            ..Default::default()
+        });
+        PoolHandle::new(connection, &config)
+    }
+
+    /// Test 1: Calls go through to the connection when no breaker is configured.
+    #[tokio::test]
+    async fn test_handle_delegates_to_connection() {
+        let connection = MockDatabase::new(DatabaseBackend::MySql)
+            .append_exec_results([MockExecResult {
+                last_insert_id: 0,
+                rows_affected: 3,
+            }])
+            .into_connection();
+        let handle = PoolHandle::new(connection, &DatabaseConfig::default());
+
+        let result = handle.execute_unprepared("DELETE FROM t").await.unwrap();
+        assert_eq!(result.rows_affected(), 3);
+        assert_eq!(handle.circuit_breaker_metrics(), None);
+    }
+
+    /// Test 2: Connection failures open the breaker, after which calls fail
+    /// fast without reaching the connection.
+    #[tokio::test]
+    async fn test_handle_circuit_breaker_opens() {
+        let connection = MockDatabase::new(DatabaseBackend::MySql)
+            .append_exec_errors([
+                DbErr::Conn(RuntimeErr::Internal("connection reset".to_string())),
+                DbErr::Conn(RuntimeErr::Internal("connection reset".to_string())),
+            ])
+            .into_connection();
+        let handle = handle_with_breaker(connection);
+
+        assert!(handle.execute_unprepared("DO 1").await.is_err());
+        assert!(handle.execute_unprepared("DO 1").await.is_err());
+
+        let err = handle.execute_unprepared("DO 1").await.unwrap_err();
+        assert!(is_circuit_open_error(&err));
+
+        let metrics = handle.circuit_breaker_metrics().unwrap();
+        assert_eq!(metrics.state, CircuitState::Open);
+        assert_eq!(metrics.failures, 2);
+        assert_eq!(metrics.rejected, 1);
+    }
+
+    /// Test 3: `run_in_transaction` applies the configured transaction defaults.
+    #[tokio::test]
+    async fn test_handle_run_in_transaction_uses_defaults() {
+        let connection = MockDatabase::new(DatabaseBackend::MySql).into_connection();
+        let config = DatabaseConfig {
+            transaction: TransactionOptions {
+                isolation_level: Some(TransactionIsolation::Serializable),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let handle = PoolHandle::new(connection, &config);
+
+        let result = handle
+            .run_in_transaction(|_txn| Box::pin(async { Ok::<_, DbErr>(()) }))
+            .await;
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            handle.transaction_options().isolation_level,
+            Some(TransactionIsolation::Serializable)
+        );
+    }
+
+    /// Test 4: Calls fail once the pool has been shut down.
+    #[tokio::test]
+    async fn test_handle_rejects_calls_after_shutdown() {
+        let connection = MockDatabase::new(DatabaseBackend::MySql).into_connection();
+        let handle = PoolHandle::new(connection, &DatabaseConfig::default());
+
+        let report = handle
+            .clone()
+            .shutdown_on(async {}, Duration::from_secs(1))
+            .await;
+        assert!(report.is_clean());
+        assert!(handle.is_closed());
+
+        let err = handle.execute_unprepared("DO 1").await.unwrap_err();
+        assert!(matches!(
+            err,
+            DbErr::ConnectionAcquire(ConnAcquireErr::ConnectionClosed)
+        ));
+        assert!(handle.begin().await.is_err());
+    }
+
+    /// Test 5: Calls holding a connection longer than the threshold are
+    /// counted as leaks.
+    #[tokio::test]
+    async fn test_handle_leak_detection() {
+        let connection = MockDatabase::new(DatabaseBackend::MySql).into_connection();
+        let mut config = DatabaseConfig::default();
+        config.pool_options.leak_detection_threshold = Some(Duration::from_millis(20));
+        let handle = PoolHandle::new(connection, &config);
+
+        let result = handle
+            .run_in_transaction(|_txn| {
+                Box::pin(async {
+                    tokio::time::sleep(Duration::from_millis(60)).await;
+                    Ok::<_, DbErr>(())
+                })
+            })
+            .await;
+        assert_eq!(result, Ok(()));
+        assert_eq!(handle.leaked_connections(), Some(1));
+        assert_eq!(
+            PoolHandle::new(
+                MockDatabase::new(DatabaseBackend::MySql).into_connection(),
+                &DatabaseConfig::default()
+            )
+            .leaked_connections(),
+            None
+        );
+    }
+
+    /// Test 6: Resize changes are applied on top of the current options, and
+    /// invalid changes keep the current pool.
+    #[tokio::test]
+    async fn test_handle_resize() {
+        let mut options = PoolOptions::default();
+        PoolResize {
+            max_connections: Some(50),
+            idle_timeout: Some(Duration::from_secs(60)),
+            ..Default::default()
+        }
+        .apply(&mut options);
+        assert_eq!(options.max_connections, 50);
+        assert_eq!(options.idle_timeout, Duration::from_secs(60));
+        assert_eq!(
+            options.min_connections,
+            PoolOptions::default().min_connections
+        );
+
+        let connection = MockDatabase::new(DatabaseBackend::MySql).into_connection();
+        let handle = PoolHandle::new(connection, &DatabaseConfig::default());
+        let result = handle
+            .resize(PoolResize {
+                max_connections: Some(0),
+                ..Default::default()
+            })
+            .await;
+        assert!(result.is_err());
+        assert_eq!(handle.pool_options().await, PoolOptions::default());
+        assert!(handle.is_mock_connection());
+    }
+
+    /// Test 7: A transaction opened with `begin_tracked` is tracked until it
+    /// ends, not only until it starts.
+    #[tokio::test]
+    async fn test_begin_tracked_detects_held_transactions() {
+        let connection = MockDatabase::new(DatabaseBackend::MySql).into_connection();
+        let mut config = DatabaseConfig::default();
+        config.pool_options.leak_detection_threshold = Some(Duration::from_millis(20));
+        let handle = PoolHandle::new(connection, &config);
+
+        let txn = handle.begin_tracked().await.expect("Failed to begin");
+        txn.commit().await.expect("Failed to commit");
+        assert_eq!(handle.leaked_connections(), Some(0));
+
+        let txn = handle.begin_tracked().await.expect("Failed to begin");
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        assert_eq!(txn.get_database_backend(), DatabaseBackend::MySql);
+        txn.rollback().await.expect("Failed to roll back");
+        assert_eq!(handle.leaked_connections(), Some(1));
+    }
+
+    /// Returns the configuration of a lazy pool, which can be created and
+    /// resized without a server.
+    fn lazy_config() -> DatabaseConfig {
+        let mut config = DatabaseConfig {
+            host: "127.0.0.1".to_string(),
+            username: "app".to_string(),
+            database_name: "app".to_string(),
+            ..Default::default()
+        };
+        config.pool_options.is_lazy = true;
+        config
+    }
+
+    /// Test 8: Shutting down closes the pools replaced by a resize that are
+    /// still draining, and a later resize fails.
+    #[tokio::test]
+    async fn test_shutdown_closes_draining_pools() {
+        let handle = crate::pool::create_pool_handle(lazy_config())
+            .await
+            .expect("Failed to create the lazy pool");
+        // A call in flight keeps the old pool draining.
+        let lease = handle.lease();
+        let old = Arc::clone(&lease.connection);
+        handle
+            .resize(PoolResize {
+                max_connections: Some(20),
+                ..Default::default()
+            })
+            .await
+            .expect("Failed to resize");
+        assert!(!old.get_mysql_connection_pool().is_closed());
+
+        let report = handle.shutdown(Duration::from_secs(1)).await;
+        assert!(report.is_clean());
+        assert!(old.get_mysql_connection_pool().is_closed());
+        assert!(handle.connection().get_mysql_connection_pool().is_closed());
+        drop(lease);
+
+        let result = handle
+            .resize(PoolResize {
+                max_connections: Some(30),
+                ..Default::default()
+            })
+            .await;
+        assert!(matches!(
+            result,
+            Err(DbErr::ConnectionAcquire(ConnAcquireErr::ConnectionClosed))
+        ));
+    }
+
+    /// Test 9: A resize swaps in a pool with the new options, and the old pool
+    /// is closed once unused, even while its `DatabaseConnection` is held, or
+    /// after `acquireTimeout` while a call is still in flight.
+    #[tokio::test]
+    async fn test_resize_replaces_and_closes_the_pool() {
+        let mut config = lazy_config();
+        config.pool_options.acquire_timeout = Duration::from_millis(100);
+        let handle = crate::pool::create_pool_handle(config)
+            .await
+            .expect("Failed to create the lazy pool");
+
+        let held = handle.connection();
+        handle
+            .resize(PoolResize {
+                max_connections: Some(20),
+                idle_timeout: Some(Duration::from_secs(60)),
+                ..Default::default()
+            })
+            .await
+            .expect("Failed to resize");
+        let options = handle.pool_options().await;
+        assert_eq!(options.max_connections, 20);
+        assert_eq!(options.idle_timeout, Duration::from_secs(60));
+        assert!(!Arc::ptr_eq(&held, &handle.connection()));
+        assert_eq!(
+            handle
+                .connection()
+                .get_mysql_connection_pool()
+                .options()
+                .get_max_connections(),
+            20
+        );
+        wait_until_closed(&held).await;
+
+        let lease = handle.lease();
+        handle
+            .resize(PoolResize {
+                min_connections: Some(2),
+                ..Default::default()
+            })
+            .await
+            .expect("Failed to resize");
+        wait_until_closed(&lease.connection).await;
+        drop(lease);
+    }
+
+    async fn wait_until_closed(connection: &DatabaseConnection) {
+        tokio::time::timeout(Duration::from_secs(2), async {
+            while !connection.get_mysql_connection_pool().is_closed() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("The replaced pool was not closed");
+    }
+
+    /// Test 10: Settings applied by `reconfigure` together with a resize are
+    /// kept by later resizes.
+    #[tokio::test]
+    async fn test_resize_keeps_reconfigured_settings() {
+        let handle = crate::pool::create_pool_handle(lazy_config())
+            .await
+            .expect("Failed to create the lazy pool");
+        let mut config = handle.config().await;
+        config.pool_options.max_connections = 15;
+        config.pool_options.test_before_acquire = false;
+        config.pool_options.connect_timeout = Some(Duration::from_secs(7));
+        handle
+            .reconfigure(config)
+            .await
+            .expect("Failed to reconfigure");
+        let pool = handle.connection().get_mysql_connection_pool().clone();
+        assert_eq!(pool.options().get_max_connections(), 15);
+        assert!(!pool.options().get_test_before_acquire());
+
+        handle
+            .resize(PoolResize {
+                max_connections: Some(25),
+                ..Default::default()
+            })
+            .await
+            .expect("Failed to resize");
+        let pool = handle.connection().get_mysql_connection_pool().clone();
+        assert_eq!(pool.options().get_max_connections(), 25);
+        assert!(!pool.options().get_test_before_acquire());
+        let options = handle.pool_options().await;
+        assert_eq!(options.connect_timeout, Some(Duration::from_secs(7)));
+        handle.shutdown(Duration::from_secs(1)).await;
+    }
+
+    /// Test 11: The resize log lists every changed option.
+    #[test]
+    fn test_resize_description() {
+        let before = PoolOptions::default();
+        let mut after = before.clone();
+        PoolResize {
+            max_connections: Some(before.max_connections + 5),
+            max_lifetime: Some(Duration::from_secs(60)),
+            ..Default::default()
+        }
+        .apply(&mut after);
+        assert_eq!(
+            PoolResize::describe(&before, &after),
+            format!(
+                "max connections {} -> {}, max lifetime {:?} -> 60s",
+                before.max_connections,
+                before.max_connections + 5,
+                before.max_lifetime
+            )
+        );
+        assert_eq!(PoolResize::describe(&before, &before), "no changes");
+    }
+}
@@ -0,0 +1,169 @@
+//! # Connection Leak Detection
+//!
+//! This module warns about connections held for longer than the
+//! `leakDetectionThreshold` configured in `PoolOptions`, a common cause of pool
+//! starvation when a connection is kept across long awaits.
+//!
+//! When a connection is acquired, the current tracing span and a backtrace are
+//! recorded. Backtraces follow the standard `RUST_BACKTRACE` setting, so they
+//! cost nothing unless enabled. The warning is logged in the recorded span as
+//! soon as the threshold passes, and again when the connection is released.
+//!
+//! A tracked call holds its connection for as long as it runs, so a single
+//! query slower than the threshold is reported as well; the warning says so,
+//! since it cannot tell a slow query from a leak.
+
+use futures_util::future::{Either, select};
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::future::Future;
+use std::pin::pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tracing::{Span, warn};
+
+/// Detects connections held for longer than a threshold.
+#[derive(Debug)]
+pub(crate) struct LeakDetector {
+    threshold: Duration,
+    detected: AtomicU64,
+}
+
+impl LeakDetector {
+    /// Creates a detector warning about connections held longer than
+    /// `threshold`.
+    pub(crate) fn new(threshold: Duration) -> Self {
+        Self {
+            threshold,
+            detected: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the number of connections held longer than the threshold so far.
+    pub(crate) fn detected(&self) -> u64 {
+        self.detected.load(Ordering::Relaxed)
+    }
+
+    /// Runs a future that holds a connection until it completes, warning if it
+    /// runs longer than the threshold.
+    ///
+    /// The future may simply be a slow query, which is counted too.
+    pub(crate) async fn track<F: Future>(&self, call: F) -> F::Output {
+        let acquisition = Acquisition::capture();
+        let mut call = pin!(call);
+        match select(call.as_mut(), pin!(tokio::time::sleep(self.threshold))).await {
+            Either::Left((output, _)) => output,
+            Either::Right(((), _)) => {
+                self.report(&acquisition, "a database call (a slow query or a leak)");
+                let output = call.await;
+                acquisition.report_release();
+                output
+            }
+        }
+    }
+
+    /// Starts tracking a connection held by a value, such as a stream, until
+    /// the returned guard is dropped.
+    pub(crate) fn guard(self: &Arc<Self>) -> LeakGuard {
+        LeakGuard {
+            detector: Arc::clone(self),
+            acquisition: Acquisition::capture(),
+        }
+    }
+
+    /// Logs a connection held longer than the threshold by `holder`.
+    fn report(&self, acquisition: &Acquisition, holder: &str) {
+        self.detected.fetch_add(1, Ordering::Relaxed);
+        let location = match acquisition.backtrace.status() {
+            BacktraceStatus::Captured => format!("acquired at:\n{}", acquisition.backtrace),
+            _ => "set RUST_BACKTRACE=1 to record where it was acquired".to_string(),
+        };
+        warn!(
+            parent: &acquisition.span,
+            "Possible connection leak: {} has held a connection for more than {:?}; {}",
+            holder,
+            self.threshold,
+            location
+        );
+    }
+}
+
+/// Tracks a connection until it is dropped.
+#[derive(Debug)]
+pub(crate) struct LeakGuard {
+    detector: Arc<LeakDetector>,
+    acquisition: Acquisition,
+}
+
+impl Drop for LeakGuard {
+    fn drop(&mut self) {
+        if self.acquisition.acquired_at.elapsed() > self.detector.threshold {
+            self.detector.report(&self.acquisition, "a connection");
+            self.acquisition.report_release();
+        }
+    }
+}
+
+/// Where and when a connection was acquired.
+#[derive(Debug)]
+struct Acquisition {
+    acquired_at: Instant,
+    span: Span,
+    backtrace: Backtrace,
+}
+
+impl Acquisition {
+    fn capture() -> Self {
+        Self {
+            acquired_at: Instant::now(),
+            span: Span::current(),
+            backtrace: Backtrace::capture(),
+        }
+    }
+
+    /// Logs the release of a connection reported as leaked.
+    fn report_release(&self) {
+        warn!(
+            parent: &self.span,
+            "Connection reported as leaked was released after {:?}",
+            self.acquired_at.elapsed()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test 1: Only calls running longer than the threshold are reported.
+    #[tokio::test]
+    async fn test_track_reports_slow_calls() {
+        let detector = LeakDetector::new(Duration::from_millis(20));
+
+        assert_eq!(detector.track(async { 1 }).await, 1);
+        assert_eq!(detector.detected(), 0);
+
+        let output = detector
+            .track(async {
+                tokio::time::sleep(Duration::from_millis(60)).await;
+                2
+            })
+            .await;
+        assert_eq!(output, 2);
+        assert_eq!(detector.detected(), 1);
+    }
+
+    /// Test 2: A guard held longer than the threshold is reported on drop.
+    #[tokio::test]
+    async fn test_guard_reports_on_drop() {
+        let detector = Arc::new(LeakDetector::new(Duration::from_millis(20)));
+
+        drop(detector.guard());
+        assert_eq!(detector.detected(), 0);
+
+        let guard = detector.guard();
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        drop(guard);
+        assert_eq!(detector.detected(), 1);
+    }
+}
@@ -2,10 +2,42 @@
 extern crate serde;
 extern crate tracing;
 
+pub use builder::*;
+pub use circuit_breaker::*;
 pub use config::*;
+pub use diff::*;
+pub use error::*;
+pub use handle::*;
+pub use loader::*;
 pub use pool::*;
+pub use preflight::*;
+pub use reload::*;
+pub use retry::*;
+#[cfg(feature = "schema")]
+pub use schema::*;
+pub use server_info::*;
+pub use shutdown::*;
 pub use tables_family::*;
+pub use transaction::*;
+pub use unknown_fields::*;
 
+mod builder;
+mod circuit_breaker;
 mod config;
+mod connection_url;
+mod diff;
+mod error;
+mod handle;
+mod leak_detection;
+mod loader;
 mod pool;
+mod preflight;
+mod reload;
+mod retry;
+#[cfg(feature = "schema")]
+mod schema;
+mod server_info;
+mod shutdown;
 mod tables_family;
+mod transaction;
+mod unknown_fields;
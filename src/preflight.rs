@@ -0,0 +1,472 @@
+//! # Preflight Checks
+//!
+//! This module verifies, right after a pool connects, that the database, tables
+//! and privileges configured in `PreflightOptions` are in place, so that a
+//! deployment with a missing table or grant fails at startup instead of on the
+//! first query that needs it.
+//!
+//! Privileges are read from `SHOW GRANTS`, which lists the grants of the
+//! current user at every level (global, database and table). When roles are
+//! active (MySQL 8, TiDB), the privileges they grant are included with
+//! `SHOW GRANTS ... USING`. Database names in database-level grants are `LIKE`
+//! patterns, so a grant on `` `app_%`.* `` covers the database `app_orders`.
+
+use crate::config::PreflightOptions;
+use sea_orm::{ConnectionTrait, DbErr, Statement};
+use std::fmt;
+
+/// An item required by `PreflightOptions` that is missing on the server.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MissingItem {
+    /// The configured database does not exist.
+    Database(String),
+    /// A listed table does not exist in the database.
+    Table(String),
+    /// The user does not hold a privilege.
+    Privilege {
+        /// The privilege, in upper case (e.g. `INSERT`).
+        privilege: String,
+        /// The database the privilege is required on.
+        database: String,
+        /// The table the privilege is required on, or `None` for the whole
+        /// database.
+        table: Option<String>,
+    },
+}
+
+impl fmt::Display for MissingItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Database(name) => write!(f, "database `{name}` does not exist"),
+            Self::Table(name) => write!(f, "table `{name}` does not exist"),
+            Self::Privilege {
+                privilege,
+                database,
+                table: Some(table),
+            } => write!(f, "missing privilege {privilege} on `{database}`.`{table}`"),
+            Self::Privilege {
+                privilege,
+                database,
+                table: None,
+            } => write!(f, "missing privilege {privilege} on `{database}`.*"),
+        }
+    }
+}
+
+/// Runs the preflight checks against `database`, returning every missing item.
+///
+/// An empty list means that all checks passed.
+///
+/// # Errors
+/// Returns a `DbErr` if the server cannot be queried.
+pub async fn run_preflight_checks<C>(
+    db: &C,
+    database: &str,
+    options: &PreflightOptions,
+) -> Result<Vec<MissingItem>, DbErr>
+where
+    C: ConnectionTrait,
+{
+    let backend = db.get_database_backend();
+    let mut missing = Vec::new();
+
+    let schemata = db
+        .query_all(Statement::from_sql_and_values(
+            backend,
+            "SELECT schema_name AS name FROM information_schema.schemata WHERE schema_name = ?",
+            [database.into()],
+        ))
+        .await?;
+    if schemata.is_empty() {
+        // Nothing else can be found in a database that does not exist.
+        missing.push(MissingItem::Database(database.to_string()));
+        return Ok(missing);
+    }
+
+    if !options.tables.is_empty() {
+        let existing = db
+            .query_all(Statement::from_sql_and_values(
+                backend,
+                "SELECT table_name AS name FROM information_schema.tables WHERE table_schema = ?",
+                [database.into()],
+            ))
+            .await?
+            .iter()
+            .map(|row| row.try_get::<String>("", "name"))
+            .collect::<Result<Vec<_>, _>>()?;
+        for table in &options.tables {
+            if !existing.iter().any(|name| name.eq_ignore_ascii_case(table)) {
+                missing.push(MissingItem::Table(table.clone()));
+            }
+        }
+    }
+
+    if !options.privileges.is_empty() {
+        let show_grants = match active_roles(db).await {
+            Some(roles) => format!("SHOW GRANTS FOR CURRENT_USER() USING {roles}"),
+            None => "SHOW GRANTS".to_string(),
+        };
+        let grants = db
+            .query_all(Statement::from_string(backend, show_grants))
+            .await?
+            .iter()
+            .map(|row| row.try_get_by_index::<String>(0))
+            .collect::<Result<Vec<_>, _>>()?
+            .iter()
+            .filter_map(|line| Grant::parse(line))
+            .collect::<Vec<_>>();
+        let targets = if options.tables.is_empty() {
+            vec![None]
+        } else {
+            options.tables.iter().map(Some).collect()
+        };
+        for table in targets {
+            for privilege in &options.privileges {
+                let privilege = privilege.trim().to_ascii_uppercase();
+                let held = grants
+                    .iter()
+                    .any(|grant| grant.covers(&privilege, database, table.map(String::as_str)));
+                if !held {
+                    missing.push(MissingItem::Privilege {
+                        privilege,
+                        database: database.to_string(),
+                        table: table.cloned(),
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(missing)
+}
+
+/// Returns the roles active in the session, as listed by `CURRENT_ROLE()`, or
+/// `None` if there are none or the server does not support roles.
+async fn active_roles<C>(db: &C) -> Option<String>
+where
+    C: ConnectionTrait,
+{
+    let row = db
+        .query_one(Statement::from_string(
+            db.get_database_backend(),
+            "SELECT CURRENT_ROLE() AS role",
+        ))
+        .await
+        .ok()??;
+    let roles = row.try_get::<String>("", "role").ok()?;
+    let roles = roles.trim();
+    (!roles.is_empty() && !roles.eq_ignore_ascii_case("NONE")).then(|| roles.to_string())
+}
+
+/// A grant listed by `SHOW GRANTS`, such as
+/// ``GRANT SELECT, INSERT ON `app`.* TO 'app'@'%'``.
+#[derive(Debug, PartialEq, Eq)]
+struct Grant {
+    /// The granted privileges, in upper case.
+    privileges: Vec<String>,
+    /// The database the grant applies to, or `None` for all databases.
+    ///
+    /// For database-level grants this is a `LIKE` pattern, in which `_` and
+    /// `%` are wildcards unless escaped with a backslash. For table-level
+    /// grants it is the literal name.
+    database: Option<String>,
+    /// The table the grant applies to, or `None` for all tables.
+    table: Option<String>,
+}
+
+impl Grant {
+    /// Parses a line of `SHOW GRANTS`, returning `None` for lines that do not
+    /// grant table privileges, such as role grants and routine grants.
+    fn parse(line: &str) -> Option<Self> {
+        let upper = line.to_ascii_uppercase();
+        let rest = upper.strip_prefix("GRANT ")?;
+        let on = rest.find(" ON ")?;
+        let object_start = "GRANT ".len() + on + " ON ".len();
+        let object_end = upper[object_start..].find(" TO ")? + object_start;
+
+        // Column-level privileges such as `SELECT (id)` do not cover a table.
+        let privileges = split_top_level(&rest[..on])
+            .into_iter()
+            .map(str::trim)
+            .filter(|privilege| !privilege.contains('('))
+            .map(str::to_string)
+            .collect();
+
+        let object = line[object_start..object_end].trim();
+        let object = match object.get(..object.find(' ').unwrap_or(0)) {
+            Some(kind) if kind.eq_ignore_ascii_case("TABLE") => object[kind.len()..].trim(),
+            Some(kind) if kind.eq_ignore_ascii_case("FUNCTION") => return None,
+            Some(kind) if kind.eq_ignore_ascii_case("PROCEDURE") => return None,
+            _ => object,
+        };
+        let (database, table) = split_object(object)?;
+        let table = (table != "*").then_some(table);
+        // Wildcards are only interpreted in database-level grants.
+        let database = match &table {
+            _ if database == "*" => None,
+            Some(_) => Some(database.replace("\\_", "_").replace("\\%", "%")),
+            None => Some(database),
+        };
+        Some(Self {
+            privileges,
+            database,
+            table,
+        })
+    }
+
+    /// Returns `true` if the grant gives `privilege` on `database`, and on
+    /// `table` if one is given.
+    fn covers(&self, privilege: &str, database: &str, table: Option<&str>) -> bool {
+        let grants_privilege = self
+            .privileges
+            .iter()
+            .any(|granted| granted == privilege || granted == "ALL" || granted == "ALL PRIVILEGES");
+        let matches_database = self
+            .database
+            .as_deref()
+            .is_none_or(|name| match self.table {
+                Some(_) => name.eq_ignore_ascii_case(database),
+                None => like_matches(name, database),
+            });
+        let matches_table = match (&self.table, table) {
+            (None, _) => true,
+            (Some(granted), Some(table)) => granted.eq_ignore_ascii_case(table),
+            (Some(_), None) => false,
+        };
+        grants_privilege && matches_database && matches_table
+    }
+}
+
+/// Returns `true` if `name` matches the `LIKE` pattern of a database-level
+/// grant, ignoring ASCII case.
+fn like_matches(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+    like_matches_from(&pattern, &name)
+}
+
+fn like_matches_from(pattern: &[char], name: &[char]) -> bool {
+    match pattern {
+        [] => name.is_empty(),
+        ['%', rest @ ..] => (0..=name.len()).any(|skip| like_matches_from(rest, &name[skip..])),
+        ['_', rest @ ..] => !name.is_empty() && like_matches_from(rest, &name[1..]),
+        ['\\', escaped, rest @ ..] | [escaped, rest @ ..] => {
+            name.split_first().is_some_and(|(c, name)| {
+                c.eq_ignore_ascii_case(escaped) && like_matches_from(rest, name)
+            })
+        }
+    }
+}
+
+/// Splits a privilege list on commas that are not inside parentheses.
+fn split_top_level(list: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (index, c) in list.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                parts.push(&list[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&list[start..]);
+    parts
+}
+
+/// Splits a `database.table` object name, removing backtick quoting.
+fn split_object(object: &str) -> Option<(String, String)> {
+    let (database, rest) = take_identifier(object)?;
+    let (table, rest) = take_identifier(rest.strip_prefix('.')?)?;
+    rest.is_empty().then_some((database, table))
+}
+
+/// Reads a possibly backtick-quoted identifier from the start of `s`.
+fn take_identifier(s: &str) -> Option<(String, &str)> {
+    match s.strip_prefix('`') {
+        Some(quoted) => {
+            let mut name = String::new();
+            let mut chars = quoted.char_indices().peekable();
+            while let Some((index, c)) = chars.next() {
+                if c != '`' {
+                    name.push(c);
+                } else if matches!(chars.peek(), Some((_, '`'))) {
+                    name.push('`');
+                    chars.next();
+                } else {
+                    return Some((name, &quoted[index + 1..]));
+                }
+            }
+            None
+        }
+        None => {
+            let end = s.find('.').unwrap_or(s.len());
+            (end > 0).then(|| (s[..end].to_string(), &s[end..]))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sea_orm::{DatabaseBackend, MockDatabase, Value};
+    use std::collections::BTreeMap;
+
+    fn rows(column: &str, values: &[&str]) -> Vec<BTreeMap<String, Value>> {
+        values
+            .iter()
+            .map(|value| BTreeMap::from([(column.to_string(), Value::from(*value))]))
+            .collect()
+    }
+
+    /// Test 1: Grant lines are parsed at every level.
+    #[test]
+    fn test_parse_grants() {
+        assert_eq!(
+            Grant::parse("GRANT USAGE ON *.* TO 'app'@'%'"),
+            Some(Grant {
+                privileges: vec!["USAGE".to_string()],
+                database: None,
+                table: None,
+            })
+        );
+        assert_eq!(
+            Grant::parse("GRANT SELECT, INSERT ON `app\\_db`.* TO 'app'@'%' WITH GRANT OPTION"),
+            Some(Grant {
+                privileges: vec!["SELECT".to_string(), "INSERT".to_string()],
+                database: Some("app\\_db".to_string()),
+                table: None,
+            })
+        );
+        assert_eq!(
+            Grant::parse("GRANT SELECT (id, name), UPDATE ON app.users TO 'app'@'%'"),
+            Some(Grant {
+                privileges: vec!["UPDATE".to_string()],
+                database: Some("app".to_string()),
+                table: Some("users".to_string()),
+            })
+        );
+        assert_eq!(Grant::parse("GRANT 'reader'@'%' TO 'app'@'%'"), None);
+        assert_eq!(
+            Grant::parse("GRANT EXECUTE ON PROCEDURE `app`.`refresh` TO 'app'@'%'"),
+            None
+        );
+        assert_eq!(
+            Grant::parse("GRANT DELETE ON TABLE `app`.`audit log` TO 'app'@'%'")
+                .and_then(|grant| grant.table),
+            Some("audit log".to_string())
+        );
+    }
+
+    /// Test 2: Grants cover privileges on matching databases and tables.
+    #[test]
+    fn test_grant_covers() {
+        let all = Grant::parse("GRANT ALL PRIVILEGES ON *.* TO 'root'@'%'").unwrap();
+        assert!(all.covers("INSERT", "app", Some("users")));
+
+        let table = Grant::parse("GRANT SELECT ON `app`.`users` TO 'app'@'%'").unwrap();
+        assert!(table.covers("SELECT", "app", Some("users")));
+        assert!(!table.covers("SELECT", "app", Some("orders")));
+        assert!(!table.covers("SELECT", "app", None));
+        assert!(!table.covers("INSERT", "app", Some("users")));
+    }
+
+    /// Test 3: All missing tables and privileges are reported together.
+    #[tokio::test]
+    async fn test_run_preflight_checks() {
+        let db = MockDatabase::new(DatabaseBackend::MySql)
+            .append_query_results([
+                rows("name", &["app"]),
+                rows("name", &["users"]),
+                rows("role", &["NONE"]),
+                rows(
+                    "Grants for app@%",
+                    &[
+                        "GRANT USAGE ON *.* TO 'app'@'%'",
+                        "GRANT SELECT ON `app`.* TO 'app'@'%'",
+                        "GRANT INSERT ON `app`.`users` TO 'app'@'%'",
+                    ],
+                ),
+            ])
+            .into_connection();
+        let options = PreflightOptions {
+            tables: vec!["users".to_string(), "orders".to_string()],
+            privileges: vec!["select".to_string(), "INSERT".to_string()],
+        };
+
+        let missing = run_preflight_checks(&db, "app", &options).await.unwrap();
+        assert_eq!(
+            missing,
+            vec![
+                MissingItem::Table("orders".to_string()),
+                MissingItem::Privilege {
+                    privilege: "INSERT".to_string(),
+                    database: "app".to_string(),
+                    table: Some("orders".to_string()),
+                },
+            ]
+        );
+        assert_eq!(
+            missing[1].to_string(),
+            "missing privilege INSERT on `app`.`orders`"
+        );
+    }
+
+    /// Test 4: Database names in database-level grants are `LIKE` patterns.
+    #[test]
+    fn test_wildcard_database_grants() {
+        let pattern = Grant::parse("GRANT SELECT ON `app_%`.* TO 'app'@'%'").unwrap();
+        assert!(pattern.covers("SELECT", "app_orders", Some("users")));
+        assert!(pattern.covers("SELECT", "APPX", None));
+        assert!(!pattern.covers("SELECT", "app", None));
+
+        let escaped = Grant::parse("GRANT SELECT ON `app\\_db`.* TO 'app'@'%'").unwrap();
+        assert!(escaped.covers("SELECT", "app_db", None));
+        assert!(!escaped.covers("SELECT", "appxdb", None));
+
+        // Wildcards are not interpreted in table-level grants.
+        let table = Grant::parse("GRANT SELECT ON `app\\_db`.`users` TO 'app'@'%'").unwrap();
+        assert!(table.covers("SELECT", "app_db", Some("users")));
+        assert!(!table.covers("SELECT", "appxdb", Some("users")));
+    }
+
+    /// Test 5: Privileges granted through active roles are included.
+    #[tokio::test]
+    async fn test_privileges_from_roles() {
+        let db = MockDatabase::new(DatabaseBackend::MySql)
+            .append_query_results([
+                rows("name", &["app"]),
+                rows("role", &["`writer`@`%`"]),
+                rows(
+                    "Grants for app@%",
+                    &[
+                        "GRANT USAGE ON *.* TO 'app'@'%'",
+                        "GRANT SELECT, INSERT ON `app`.* TO 'app'@'%'",
+                        "GRANT `writer`@`%` TO 'app'@'%'",
+                    ],
+                ),
+            ])
+            .into_connection();
+        let options = PreflightOptions {
+            tables: Vec::new(),
+            privileges: vec!["INSERT".to_string()],
+        };
+
+        let missing = run_preflight_checks(&db, "app", &options).await.unwrap();
+        assert!(missing.is_empty());
+        let statements = db
+            .into_transaction_log()
+            .into_iter()
+            .flat_map(|txn| txn.statements().to_vec())
+            .map(|stmt| stmt.sql)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            statements.last().map(String::as_str),
+            Some("SHOW GRANTS FOR CURRENT_USER() USING `writer`@`%`")
+        );
+    }
+}
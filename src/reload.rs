@@ -0,0 +1,327 @@
+//! # Hot Reload
+//!
+//! This module provides `ConfigWatcher`, which watches a configuration file and
+//! applies changes to a running `PoolHandle` without restarting the
+//! application.
+//!
+//! The file is polled rather than watched through file system events, so that
+//! atomically replaced files, such as a mounted Kubernetes ConfigMap, are picked
+//! up reliably. When its contents change, the file is parsed and compared with
+//! the configuration of the running pool:
+//!
+//! - changes to the pool size and connection lifetimes resize the pool;
+//! - changes that affect connections, such as the host, credentials or TLS
+//!   settings, rebuild the pool;
+//! - changes to the retry, connect timeout and validation settings are applied
+//!   by the next resize or rebuild;
+//! - changes to features of the handle itself (circuit breaker, leak detection,
+//!   transaction defaults) are logged, as they require a restart.
+//!
+//! Log levels are out of scope: the configuration has no logging settings, and
+//! the application owns its `tracing` subscriber, so it reloads its filter
+//! itself, for example with `tracing_subscriber::reload`.
+//!
+//! An invalid file is logged and ignored, leaving the running pool untouched.
+//! Each reload is compared with the configuration last applied by the watcher,
+//! so a change that only takes effect later is reported once.
+
+use crate::config::DatabaseConfig;
+use crate::diff::{ChangeImpact, ConfigDiff};
+use crate::error::ConfigError;
+use crate::handle::PoolHandle;
+use crate::loader::parse_app_config;
+use crate::tables_family::TableFamilyName;
+use sea_orm::DbErr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+/// What a reload did to the running pool.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ReloadAction {
+    /// No change required touching the pool.
+    #[default]
+    Unchanged,
+    /// The pool was resized.
+    Resized,
+    /// The pool was rebuilt with new connections.
+    Rebuilt,
+}
+
+/// Watches a configuration file and applies its changes to a `PoolHandle`.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use seaorm_pool::ConfigWatcher;
+///
+/// let handle = create_pool_handle(config.database).await?;
+/// ConfigWatcher::new("Settings.toml", handle.clone()).spawn();
+/// ```
+#[derive(Clone, Debug)]
+pub struct ConfigWatcher {
+    path: PathBuf,
+    handle: PoolHandle,
+    interval: Duration,
+    table_family: Option<TableFamilyName>,
+    strict: bool,
+    /// The configuration read by the last successful reload, or `None` to
+    /// compare the first reload with the configuration of the pool.
+    last_applied: Arc<tokio::sync::Mutex<Option<DatabaseConfig>>>,
+}
+
+impl ConfigWatcher {
+    /// Creates a watcher of the `AppConfig` file at `path`, a TOML, YAML or
+    /// JSON file, applying the changes of its `database` section to
+    /// `handle`. The file is checked every two seconds.
+    pub fn new(path: impl Into<PathBuf>, handle: PoolHandle) -> Self {
+        Self {
+            path: path.into(),
+            handle,
+            interval: Duration::from_secs(2),
+            table_family: None,
+            strict: false,
+            last_applied: Arc::new(tokio::sync::Mutex::new(None)),
+        }
+    }
+
+    /// Sets how often the file is checked for changes.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Applies the configuration of a table family, as returned by
+    /// `DatabaseConfig::for_table_family`, instead of the `database` section.
+    pub fn table_family(mut self, family: TableFamilyName) -> Self {
+        self.table_family = Some(family);
+        self
+    }
+
+    /// Sets whether unknown fields in the `database` table are rejected.
+    ///
+    /// In strict mode a file with an unknown field, usually a typo, is not
+    /// applied, like a file that fails to parse. Otherwise unknown fields are
+    /// logged as warnings and ignored.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Reads the file once and applies its changes to the pool.
+    ///
+    /// The file is compared with the configuration read by the previous
+    /// successful reload, or with the pool's configuration on the first one.
+    ///
+    /// # Errors
+    /// Returns a `DbErr` if the file cannot be parsed, if it contains an
+    /// unknown field in strict mode, if the configuration is invalid, or if the
+    /// pool cannot be resized or rebuilt.
+    pub async fn reload(&self) -> Result<ReloadAction, DbErr> {
+        let contents = tokio::fs::read(&self.path)
+            .await
+            .map_err(|err| ConfigError::parse(self.path.display().to_string(), err.to_string()))?;
+        self.apply(&contents).await
+    }
+
+    /// Applies the contents of the file, already read by the caller.
+    async fn apply(&self, contents: &[u8]) -> Result<ReloadAction, DbErr> {
+        let app_config = parse_app_config(&self.path, contents, self.strict)?;
+        let config = match &self.table_family {
+            Some(family) => app_config.database.for_table_family(family)?,
+            None => app_config.database,
+        };
+        config.validate()?;
+        let mut last_applied = self.last_applied.lock().await;
+        let current = match &*last_applied {
+            Some(config) => config.clone(),
+            None => self.handle.config().await,
+        };
+        let plan = ReloadPlan::new(&current, &config);
+
+        if !plan.diff.is_empty() {
+            info!(
+                "Configuration file '{}' changed:\n{}",
+                self.path.display(),
+                plan.diff
+            );
+        }
+        let restart_required = plan
+            .diff
+            .with_impact(ChangeImpact::Restart)
+            .map(|change| change.path.as_str())
+            .collect::<Vec<_>>();
+        if !restart_required.is_empty() {
+            warn!(
+                "Changes to {} take effect after a restart.",
+                restart_required.join(", ")
+            );
+        }
+        match plan.action {
+            ReloadAction::Unchanged => {}
+            // A resize also applies the changes that take effect on the next
+            // rebuild, so both swap in a pool built from the new configuration.
+            ReloadAction::Resized | ReloadAction::Rebuilt => {
+                self.handle.reconfigure(config.clone()).await?
+            }
+        }
+        *last_applied = Some(config);
+        Ok(plan.action)
+    }
+
+    /// Checks the file for changes until the pool is shut down.
+    ///
+    /// Failed reloads are logged and leave the running pool untouched.
+    pub async fn run(self) {
+        let mut last_contents = tokio::fs::read(&self.path).await.ok();
+        loop {
+            tokio::time::sleep(self.interval).await;
+            if self.handle.is_closed() {
+                break;
+            }
+            let Ok(contents) = tokio::fs::read(&self.path).await else {
+                continue;
+            };
+            if last_contents.as_ref() == Some(&contents) {
+                continue;
+            }
+            let result = self.apply(&contents).await;
+            last_contents = Some(contents);
+            if let Err(err) = result {
+                error!(
+                    "Failed to reload configuration file '{}': {}",
+                    self.path.display(),
+                    err
+                );
+            }
+        }
+    }
+
+    /// Runs the watcher in a background task until the pool is shut down.
+    pub fn spawn(self) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(self.run())
+    }
+}
+
+/// The changes between two configurations and how to apply them.
+#[derive(Debug)]
+struct ReloadPlan {
+    diff: ConfigDiff,
+    action: ReloadAction,
+}
+
+impl ReloadPlan {
+    fn new(current: &DatabaseConfig, new: &DatabaseConfig) -> Self {
+        let diff = current.diff(new);
+        let action = if diff.requires_reconnect() {
+            ReloadAction::Rebuilt
+        } else if diff.with_impact(ChangeImpact::Resize).next().is_some() {
+            ReloadAction::Resized
+        } else {
+            ReloadAction::Unchanged
+        };
+        Self { diff, action }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{CircuitBreakerOptions, SslMode};
+    use sea_orm::{ConnectionTrait, DatabaseBackend, MockDatabase};
+
+    /// Test 1: Pool size changes resize the pool.
+    #[test]
+    fn test_plan_resize() {
+        let current = DatabaseConfig::default();
+        let mut new = current.clone();
+        new.pool_options.max_connections = 40;
+        new.pool_options.retry.max_attempts = 3;
+
+        let plan = ReloadPlan::new(&current, &new);
+        assert_eq!(plan.action, ReloadAction::Resized);
+        assert_eq!(plan.diff.changes.len(), 2);
+    }
+
+    /// Test 2: Connection changes rebuild the pool, and changes requiring a
+    /// restart leave it untouched.
+    #[test]
+    fn test_plan_rebuild_and_restart() {
+        let current = DatabaseConfig::default();
+        let new = DatabaseConfig {
+            password: "rotated".to_string(),
+            ssl_mode: Some(SslMode::Required),
+            ..current.clone()
+        };
+        let plan = ReloadPlan::new(&current, &new);
+        assert_eq!(plan.action, ReloadAction::Rebuilt);
+
+        let mut new = current.clone();
+        new.pool_options.circuit_breaker = Some(CircuitBreakerOptions::default());
+        let plan = ReloadPlan::new(&current, &new);
+        assert_eq!(plan.action, ReloadAction::Unchanged);
+    }
+
+    /// Test 3: `reload` applies a file to a running handle, and a change that
+    /// takes effect later is only reported by the reload that found it.
+    #[tokio::test]
+    async fn test_reload_applies_file_changes() {
+        let dir = std::env::temp_dir().join(format!("seaorm-pool-reload-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("Settings.toml");
+        let base = r#"
+            [database]
+            host = "127.0.0.1"
+            username = "app"
+            password = "secret"
+            databaseName = "app"
+
+            [database.poolOptions]
+            isLazy = true
+        "#;
+        std::fs::write(&path, base).unwrap();
+
+        let config = parse_app_config(&path, base.as_bytes(), true)
+            .unwrap()
+            .database;
+        let connection = MockDatabase::new(DatabaseBackend::MySql).into_connection();
+        let handle = PoolHandle::new(connection, &config);
+        let watcher = ConfigWatcher::new(&path, handle.clone());
+        assert_eq!(watcher.reload().await, Ok(ReloadAction::Unchanged));
+
+        // The circuit breaker requires a restart: the handle keeps its own
+        // settings, but the next reload no longer reports the change.
+        let restart = format!("{base}\n            [database.poolOptions.circuitBreaker]\n");
+        std::fs::write(&path, &restart).unwrap();
+        assert_eq!(watcher.reload().await, Ok(ReloadAction::Unchanged));
+        assert!(handle.circuit_breaker_metrics().is_none());
+        let applied = watcher.last_applied.lock().await.clone().unwrap();
+        assert!(applied.pool_options.circuit_breaker.is_some());
+        assert!(
+            applied
+                .diff(
+                    &parse_app_config(&path, restart.as_bytes(), true)
+                        .unwrap()
+                        .database
+                )
+                .is_empty()
+        );
+
+        let resized = restart.replace(
+            "isLazy = true",
+            "isLazy = true\n            maxConnections = 42\n            connectTimeout = \"7s\"",
+        );
+        std::fs::write(&path, resized).unwrap();
+        assert_eq!(watcher.reload().await, Ok(ReloadAction::Resized));
+        let options = handle.pool_options().await;
+        assert_eq!(options.max_connections, 42);
+        assert_eq!(options.connect_timeout, Some(Duration::from_secs(7)));
+        assert!(!handle.is_mock_connection());
+
+        std::fs::write(&path, "[database]\nhost = \"").unwrap();
+        assert!(watcher.reload().await.is_err());
+        assert_eq!(handle.pool_options().await.max_connections, 42);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,467 @@
+//! # Retry Support
+//!
+//! This module implements the exponential backoff used to retry operations
+//! against the database, driven by a `RetryOptions` policy.
+//!
+//! It also classifies `DbErr`s as transient or permanent based on MySQL and
+//! TiDB error codes, and provides `with_retry` to re-run a transaction when it
+//! fails with a transient error such as a deadlock or a TiDB write conflict.
+
+use crate::circuit_breaker::{is_availability_error, is_circuit_open_error};
+use crate::config::RetryOptions;
+use sea_orm::{DatabaseTransaction, DbErr, RuntimeErr, SqlxMySqlError, TransactionTrait, sqlx};
+use std::collections::hash_map::RandomState;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::pin::Pin;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// MySQL and TiDB error codes that indicate a transient failure: running the
+/// same operation again may succeed.
+const TRANSIENT_ERROR_CODES: [u16; 11] = [
+    1205, // MySQL: Lock wait timeout exceeded
+    1213, // MySQL: Deadlock found when trying to get lock
+    8002, // TiDB: SELECT FOR UPDATE cannot be retried after a write conflict
+    8022, // TiDB: Transaction commit failed and cannot be retried automatically
+    8028, // TiDB: Information schema is changed
+    9001, // TiDB: PD server timeout
+    9002, // TiDB: TiKV server timeout
+    9003, // TiDB: TiKV server is busy
+    9004, // TiDB: Resolve lock timeout
+    9005, // TiDB: Region is unavailable
+    9007, // TiDB: Write conflict
+];
+
+/// MySQL error codes returned while connecting that indicate the server is
+/// temporarily unable to accept the connection.
+const TRANSIENT_CONNECT_ERROR_CODES: [u16; 3] = [
+    1040, // MySQL: Too many connections
+    1053, // MySQL: Server shutdown in progress
+    1203, // MySQL: User already has more than 'max_user_connections' active connections
+];
+
+/// Whether retrying an operation that failed with a given error may succeed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorClass {
+    /// The failure is temporary; the operation may succeed if run again.
+    Transient,
+    /// The failure will happen again; the operation should not be retried.
+    Permanent,
+}
+
+/// Classifies a `DbErr` as transient or permanent.
+///
+/// Server errors are classified by their MySQL/TiDB error code (deadlocks, lock
+/// wait timeouts, TiDB write conflicts, unavailable regions, schema changes).
+/// Failures to reach the database, such as acquire timeouts and I/O errors,
+/// are transient, except for calls rejected by an open circuit breaker. All
+/// other errors are permanent.
+pub fn classify_error(err: &DbErr) -> ErrorClass {
+    if is_circuit_open_error(err) {
+        return ErrorClass::Permanent;
+    }
+    let server_code = match err {
+        DbErr::Exec(RuntimeErr::SqlxError(sqlx::Error::Database(db_err)))
+        | DbErr::Query(RuntimeErr::SqlxError(sqlx::Error::Database(db_err)))
+        | DbErr::Conn(RuntimeErr::SqlxError(sqlx::Error::Database(db_err))) => db_err
+            .try_downcast_ref::<SqlxMySqlError>()
+            .map(SqlxMySqlError::number),
+        _ => None,
+    };
+    match server_code {
+        Some(code) => classify_error_code(code),
+        None if is_availability_error(err) => ErrorClass::Transient,
+        None => ErrorClass::Permanent,
+    }
+}
+
+/// Classifies a MySQL/TiDB server error code as transient or permanent.
+pub fn classify_error_code(code: u16) -> ErrorClass {
+    if TRANSIENT_ERROR_CODES.contains(&code) {
+        ErrorClass::Transient
+    } else {
+        ErrorClass::Permanent
+    }
+}
+
+/// Returns `true` if a failed attempt to connect may succeed when made again.
+///
+/// Failures to reach the server (I/O errors, timeouts) and servers that are
+/// temporarily refusing connections are worth retrying. Errors that will
+/// happen again, such as bad credentials, an unknown database or a TLS or
+/// configuration problem, are not. Neither is a failing initialization
+/// statement, as long as it is reported with its server error by the probe
+/// connection of `connect_with_retry`.
+///
+/// `sqlx` reports the failures of its `after_connect` hook as a pool timeout,
+/// which cannot be told apart from a slow server and is retried.
+pub(crate) fn is_retryable_connect_error(err: &sqlx::Error) -> bool {
+    match err {
+        sqlx::Error::Database(db_err) => db_err
+            .try_downcast_ref::<SqlxMySqlError>()
+            .map(SqlxMySqlError::number)
+            .is_some_and(|code| {
+                TRANSIENT_CONNECT_ERROR_CODES.contains(&code)
+                    || classify_error_code(code) == ErrorClass::Transient
+            }),
+        sqlx::Error::Io(_)
+        | sqlx::Error::Protocol(_)
+        | sqlx::Error::PoolTimedOut
+        | sqlx::Error::PoolClosed
+        | sqlx::Error::WorkerCrashed => true,
+        _ => false,
+    }
+}
+
+/// Returns `true` if `classify_error` labels the error as transient.
+pub fn is_transient_error(err: &DbErr) -> bool {
+    classify_error(err) == ErrorClass::Transient
+}
+
+/// Runs a transaction, re-running it with backoff when it fails with a
+/// transient error.
+///
+/// Each attempt runs `callback` in a new transaction that is committed if the
+/// callback returns `Ok` and rolled back otherwise. If the attempt fails with an
+/// error that `classify_error` labels as transient, it is retried according to
+/// `options`, or `RetryOptions::for_transactions` if `None`; any other error is
+/// returned immediately. Because the callback may run several times, it must be
+/// safe to repeat.
+///
+/// A connection failure during `COMMIT` is never retried: the server may have
+/// applied the commit before the connection broke, and running the callback
+/// again could apply its changes twice. Server errors returned by `COMMIT`,
+/// such as a TiDB write conflict, mean the commit failed and are retried.
+///
+/// # Errors
+/// Returns the error of the last attempt.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use seaorm_pool::with_retry;
+///
+/// with_retry(&pool, None, |txn| {
+///     Box::pin(async move {
+///         txn.execute_unprepared("UPDATE accounts SET balance = balance - 10 WHERE id = 1")
+///             .await?;
+///         Ok(())
+///     })
+/// })
+/// .await?;
+/// ```
+pub async fn with_retry<C, F, T>(
+    db: &C,
+    options: Option<&RetryOptions>,
+    callback: F,
+) -> Result<T, DbErr>
+where
+    C: TransactionTrait,
+    F: for<'c> Fn(
+            &'c DatabaseTransaction,
+        ) -> Pin<Box<dyn Future<Output = Result<T, DbErr>> + Send + 'c>>
+        + Send
+        + Sync,
+    T: Send,
+{
+    let options = options
+        .cloned()
+        .unwrap_or_else(RetryOptions::for_transactions);
+    let mut backoff = Backoff::new(&options);
+    loop {
+        let attempt = backoff.start_attempt();
+        let err = match db.begin().await {
+            Ok(txn) => match callback(&txn).await {
+                Ok(value) => match txn.commit().await {
+                    Ok(()) => return Ok(value),
+                    Err(err) if !is_retryable_commit_error(&err) => return Err(err),
+                    Err(err) => err,
+                },
+                Err(err) => {
+                    if let Err(rollback_err) = txn.rollback().await {
+                        warn!("Failed to roll back transaction: {}", rollback_err);
+                    }
+                    err
+                }
+            },
+            Err(err) => err,
+        };
+        if !is_transient_error(&err) {
+            return Err(err);
+        }
+        match backoff.next_retry() {
+            Some(delay) => {
+                warn!(
+                    "Transaction attempt {}/{} failed with a transient error: {}. Retrying in {:?}...",
+                    attempt, options.max_attempts, err, delay
+                );
+                tokio::time::sleep(delay).await;
+            }
+            None => return Err(err),
+        }
+    }
+}
+
+/// Returns `true` if a transaction whose `COMMIT` failed with `err` can be
+/// run again: the error is transient and the server reported it, so the commit
+/// is known not to have been applied.
+fn is_retryable_commit_error(err: &DbErr) -> bool {
+    is_transient_error(err) && !is_availability_error(err)
+}
+
+/// Tracks the attempts made under a `RetryOptions` policy and computes the
+/// delay before each retry.
+#[derive(Debug)]
+pub(crate) struct Backoff {
+    options: RetryOptions,
+    started_at: Instant,
+    attempts: u32,
+    next_delay: Duration,
+}
+
+impl Backoff {
+    /// Starts tracking a new sequence of attempts.
+    pub(crate) fn new(options: &RetryOptions) -> Self {
+        Self {
+            options: options.clone(),
+            started_at: Instant::now(),
+            attempts: 0,
+            next_delay: options.initial_delay,
+        }
+    }
+
+    /// Records the start of an attempt and returns its 1-based number.
+    pub(crate) fn start_attempt(&mut self) -> u32 {
+        self.attempts += 1;
+        self.attempts
+    }
+
+    /// Returns the time left before the overall deadline, if one is configured.
+    pub(crate) fn remaining(&self) -> Option<Duration> {
+        self.options
+            .deadline
+            .map(|deadline| deadline.saturating_sub(self.started_at.elapsed()))
+    }
+
+    /// Returns the delay to wait before the next attempt, or `None` if the
+    /// attempts are exhausted or the deadline would pass before it starts.
+    pub(crate) fn next_retry(&mut self) -> Option<Duration> {
+        if self.attempts >= self.options.max_attempts {
+            return None;
+        }
+        let delay = if self.options.jitter {
+            jittered(self.next_delay)
+        } else {
+            self.next_delay
+        };
+        if self.remaining().is_some_and(|remaining| remaining <= delay) {
+            return None;
+        }
+        self.next_delay = self
+            .next_delay
+            .saturating_mul(2)
+            .min(self.options.max_delay);
+        Some(delay)
+    }
+}
+
+/// Randomizes a delay to a value between half and all of it.
+fn jittered(delay: Duration) -> Duration {
+    // A freshly keyed hasher is a cheap source of randomness that does not
+    // require an extra dependency.
+    let random = RandomState::new().build_hasher().finish();
+    let fraction = (random >> 11) as f64 / (1u64 << 53) as f64;
+    delay.mul_f64(0.5 + fraction / 2.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sea_orm::{ConnAcquireErr, DatabaseBackend, MockDatabase};
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// Test 1: Delays double up to `max_delay` and stop after `max_attempts`.
+    #[test]
+    fn test_backoff_doubles_until_max_delay() {
+        let options = RetryOptions {
+            max_attempts: 5,
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(300),
+            jitter: false,
+            deadline: None,
+        };
+        let mut backoff = Backoff::new(&options);
+
+        let mut delays = Vec::new();
+        loop {
+            backoff.start_attempt();
+            match backoff.next_retry() {
+                Some(delay) => delays.push(delay.as_millis()),
+                None => break,
+            }
+        }
+
+        assert_eq!(delays, vec![100, 200, 300, 300]);
+    }
+
+    /// Test 2: No retry is scheduled past the deadline.
+    #[test]
+    fn test_backoff_respects_deadline() {
+        let options = RetryOptions {
+            max_attempts: 100,
+            initial_delay: Duration::from_secs(1),
+            deadline: Some(Duration::from_millis(10)),
+            ..Default::default()
+        };
+        let mut backoff = Backoff::new(&options);
+        backoff.start_attempt();
+        assert_eq!(backoff.next_retry(), None);
+    }
+
+    /// Test 3: Jitter keeps delays between half and all of the nominal value.
+    #[test]
+    fn test_jitter_bounds() {
+        let delay = Duration::from_millis(1000);
+        for _ in 0..100 {
+            let jittered = jittered(delay);
+            assert!(jittered >= delay / 2 && jittered <= delay);
+        }
+    }
+
+    /// Test 4: MySQL and TiDB error codes are classified.
+    #[test]
+    fn test_classify_error_codes() {
+        for code in [1205, 1213, 8028, 9005, 9007] {
+            assert_eq!(classify_error_code(code), ErrorClass::Transient, "{code}");
+        }
+        for code in [1062, 1064, 1146, 1045] {
+            assert_eq!(classify_error_code(code), ErrorClass::Permanent, "{code}");
+        }
+    }
+
+    /// Test 5: Connection failures are transient, other errors are permanent.
+    #[test]
+    fn test_classify_errors() {
+        assert!(is_transient_error(&DbErr::ConnectionAcquire(
+            ConnAcquireErr::Timeout
+        )));
+        assert!(!is_transient_error(&DbErr::RecordNotFound(
+            "row".to_string()
+        )));
+        assert!(!is_transient_error(&DbErr::Custom("bad input".to_string())));
+    }
+
+    /// Test 6: `with_retry` re-runs the transaction after a transient error and
+    /// gives up immediately on a permanent one.
+    #[tokio::test]
+    async fn test_with_retry() {
+        let options = RetryOptions {
+            max_attempts: 3,
+            initial_delay: Duration::from_millis(1),
+            jitter: false,
+            ..Default::default()
+        };
+        let db = MockDatabase::new(DatabaseBackend::MySql).into_connection();
+
+        let attempts = AtomicU32::new(0);
+        let result = with_retry(&db, Some(&options), |_txn| {
+            let attempt = attempts.fetch_add(1, Ordering::SeqCst) + 1;
+            Box::pin(async move {
+                if attempt < 3 {
+                    Err(DbErr::ConnectionAcquire(ConnAcquireErr::Timeout))
+                } else {
+                    Ok(attempt)
+                }
+            })
+        })
+        .await;
+        assert_eq!(result, Ok(3));
+
+        let attempts = AtomicU32::new(0);
+        let result: Result<(), DbErr> = with_retry(&db, Some(&options), |_txn| {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Box::pin(async { Err(DbErr::Custom("permanent".to_string())) })
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+
+    /// Test 7: Very long delays saturate instead of overflowing.
+    #[test]
+    fn test_backoff_saturates() {
+        let options = RetryOptions {
+            max_attempts: 3,
+            initial_delay: Duration::MAX,
+            max_delay: Duration::MAX,
+            jitter: false,
+            deadline: None,
+        };
+        let mut backoff = Backoff::new(&options);
+        backoff.start_attempt();
+        assert_eq!(backoff.next_retry(), Some(Duration::MAX));
+        backoff.start_attempt();
+        assert_eq!(backoff.next_retry(), Some(Duration::MAX));
+    }
+
+    /// Test 8: Unreachable servers are worth reconnecting to, configuration
+    /// problems are not.
+    #[test]
+    fn test_retryable_connect_errors() {
+        let io = std::io::Error::new(std::io::ErrorKind::ConnectionRefused, "refused");
+        assert!(is_retryable_connect_error(&sqlx::Error::Io(io)));
+        assert!(is_retryable_connect_error(&sqlx::Error::PoolTimedOut));
+        assert!(!is_retryable_connect_error(&sqlx::Error::Configuration(
+            "bad ssl-mode".into()
+        )));
+    }
+
+    /// Test 9: Without options, `with_retry` uses its own policy and retries.
+    #[tokio::test]
+    async fn test_with_retry_default_policy() {
+        let db = MockDatabase::new(DatabaseBackend::MySql).into_connection();
+        let attempts = AtomicU32::new(0);
+        let result = with_retry(&db, None, |_txn| {
+            let attempt = attempts.fetch_add(1, Ordering::SeqCst) + 1;
+            Box::pin(async move {
+                if attempt < 3 {
+                    Err(DbErr::ConnectionAcquire(ConnAcquireErr::Timeout))
+                } else {
+                    Ok(attempt)
+                }
+            })
+        })
+        .await;
+        assert_eq!(result, Ok(3));
+        assert!(RetryOptions::for_transactions().max_attempts > 1);
+    }
+
+    /// Test 10: A connection failure during `COMMIT` is transient but is not
+    /// retried, because the commit may have been applied.
+    #[test]
+    fn test_commit_connection_errors_are_not_retried() {
+        let io = std::io::Error::new(std::io::ErrorKind::ConnectionReset, "reset");
+        let err = DbErr::Exec(RuntimeErr::SqlxError(sqlx::Error::Io(io)));
+        assert!(is_transient_error(&err));
+        assert!(!is_retryable_commit_error(&err));
+        assert!(!is_retryable_commit_error(&DbErr::Custom(
+            "bad".to_string()
+        )));
+    }
+
+    /// Test 11: A pool timeout, which is how `sqlx` reports a failing
+    /// `after_connect` hook, is retried, while the error of a missing
+    /// resource group is not.
+    #[test]
+    fn test_hook_failures_are_retried_as_timeouts() {
+        assert!(is_retryable_connect_error(&sqlx::Error::PoolTimedOut));
+        let missing = crate::error::ConfigError::invalid_value(
+            "resourceGroup",
+            "resource group `rg1` does not exist on the server",
+        );
+        assert!(!is_retryable_connect_error(&sqlx::Error::Configuration(
+            Box::new(missing)
+        )));
+    }
+}
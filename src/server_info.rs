@@ -0,0 +1,254 @@
+//! # Server Detection
+//!
+//! This module identifies the database server behind a pool. TiDB, MySQL and
+//! MariaDB all speak the MySQL protocol, so the flavor and versions are parsed
+//! from the string returned by `SELECT VERSION()`, for example
+//! `8.0.11-TiDB-v7.5.1` or `10.11.6-MariaDB-1:10.11.6+maria~ubu2204`.
+
+use crate::error::ConfigError;
+use sea_orm::{ConnectionTrait, DbErr, Statement};
+use std::fmt;
+use std::str::FromStr;
+
+/// The kind of database server.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ServerFlavor {
+    /// Oracle MySQL, or a compatible server not identified as another flavor.
+    MySql,
+    /// MariaDB.
+    MariaDb,
+    /// TiDB.
+    TiDb,
+}
+
+impl fmt::Display for ServerFlavor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::MySql => "MySQL",
+            Self::MariaDb => "MariaDB",
+            Self::TiDb => "TiDB",
+        })
+    }
+}
+
+/// A `major.minor.patch` version number.
+///
+/// In configuration files versions are written as strings, where the minor and
+/// patch numbers may be omitted (e.g. `"7.5"`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct ServerVersion {
+    /// The major version number.
+    pub major: u32,
+    /// The minor version number.
+    pub minor: u32,
+    /// The patch version number.
+    pub patch: u32,
+}
+
+impl ServerVersion {
+    /// Creates a version from its components.
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// Parses the leading version number of a string, ignoring any suffix
+    /// (e.g. `8.0.36-log`).
+    fn parse_prefix(s: &str) -> Option<Self> {
+        let end = s
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(s.len());
+        s[..end].trim_end_matches('.').parse().ok()
+    }
+}
+
+impl fmt::Display for ServerVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+impl FromStr for ServerVersion {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            ConfigError::invalid_value(
+                "minServerVersion",
+                format!("`{s}` is not a version number such as `8.0.11`"),
+            )
+        };
+        let mut components = s.trim().split('.');
+        let mut next = |required: bool| match components.next() {
+            Some(component) => component.parse::<u32>().map_err(|_| invalid()),
+            None if required => Err(invalid()),
+            None => Ok(0),
+        };
+        let version = Self::new(next(true)?, next(false)?, next(false)?);
+        if components.next().is_some() {
+            return Err(invalid());
+        }
+        Ok(version)
+    }
+}
+
+impl TryFrom<String> for ServerVersion {
+    type Error = ConfigError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<ServerVersion> for String {
+    fn from(version: ServerVersion) -> Self {
+        version.to_string()
+    }
+}
+
+/// The flavor and version of a database server.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ServerInfo {
+    /// The kind of server.
+    pub flavor: ServerFlavor,
+    /// The MySQL version reported by the server. For TiDB, this is the MySQL
+    /// version it is compatible with.
+    pub version: ServerVersion,
+    /// The TiDB release, if the server is TiDB.
+    pub tidb_version: Option<ServerVersion>,
+}
+
+impl ServerInfo {
+    /// Parses the string returned by `SELECT VERSION()`.
+    ///
+    /// Returns `None` if the string does not start with a version number.
+    pub fn parse(version: &str) -> Option<Self> {
+        // MariaDB before 11 prefixes its version with `5.5.5-` for old clients.
+        let version = version.strip_prefix("5.5.5-").unwrap_or(version);
+        let mysql_version = ServerVersion::parse_prefix(version)?;
+        if let Some(index) = version.find("-TiDB-") {
+            let tidb = &version[index + "-TiDB-".len()..];
+            return Some(Self {
+                flavor: ServerFlavor::TiDb,
+                version: mysql_version,
+                tidb_version: ServerVersion::parse_prefix(tidb.trim_start_matches('v')),
+            });
+        }
+        let flavor = if version.to_ascii_lowercase().contains("mariadb") {
+            ServerFlavor::MariaDb
+        } else {
+            ServerFlavor::MySql
+        };
+        Some(Self {
+            flavor,
+            version: mysql_version,
+            tidb_version: None,
+        })
+    }
+
+    /// Returns the version of the server's own release: the TiDB release for
+    /// TiDB, and the MySQL or MariaDB version otherwise.
+    pub fn release_version(&self) -> ServerVersion {
+        self.tidb_version.unwrap_or(self.version)
+    }
+
+    /// Checks that the server's release is at least `minimum`.
+    ///
+    /// # Errors
+    /// Returns a `ConfigError` for the `minServerVersion` field if the server is
+    /// older.
+    pub fn require_version(&self, minimum: ServerVersion) -> Result<(), ConfigError> {
+        if self.release_version() < minimum {
+            return Err(ConfigError::invalid_value(
+                "minServerVersion",
+                format!("the server runs {self}, but at least {minimum} is required"),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ServerInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.flavor, self.release_version())
+    }
+}
+
+/// Queries and parses the version of the server behind a connection.
+///
+/// # Errors
+/// Returns a `DbErr` if the query fails or its result cannot be parsed.
+pub async fn detect_server_info<C>(db: &C) -> Result<ServerInfo, DbErr>
+where
+    C: ConnectionTrait,
+{
+    let statement =
+        Statement::from_string(db.get_database_backend(), "SELECT VERSION() AS version");
+    let row = db
+        .query_one(statement)
+        .await?
+        .ok_or_else(|| DbErr::Custom("`SELECT VERSION()` returned no rows".to_string()))?;
+    let version: String = row.try_get("", "version")?;
+    ServerInfo::parse(&version)
+        .ok_or_else(|| DbErr::Custom(format!("unrecognized server version `{version}`")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sea_orm::{DatabaseBackend, MockDatabase, Value};
+    use std::collections::BTreeMap;
+
+    /// Test 1: TiDB, MySQL and MariaDB version strings are parsed.
+    #[test]
+    fn test_parse_version_strings() {
+        let tidb = ServerInfo::parse("8.0.11-TiDB-v7.5.1").unwrap();
+        assert_eq!(tidb.flavor, ServerFlavor::TiDb);
+        assert_eq!(tidb.version, ServerVersion::new(8, 0, 11));
+        assert_eq!(tidb.tidb_version, Some(ServerVersion::new(7, 5, 1)));
+        assert_eq!(tidb.to_string(), "TiDB 7.5.1");
+
+        let mysql = ServerInfo::parse("8.0.36-0ubuntu0.22.04.1").unwrap();
+        assert_eq!(mysql.flavor, ServerFlavor::MySql);
+        assert_eq!(mysql.version, ServerVersion::new(8, 0, 36));
+        assert_eq!(mysql.tidb_version, None);
+
+        let mariadb = ServerInfo::parse("5.5.5-10.11.6-MariaDB-1:10.11.6+maria~ubu2204").unwrap();
+        assert_eq!(mariadb.flavor, ServerFlavor::MariaDb);
+        assert_eq!(mariadb.version, ServerVersion::new(10, 11, 6));
+
+        assert_eq!(ServerInfo::parse("unknown"), None);
+    }
+
+    /// Test 2: Versions are parsed from configuration strings and compared
+    /// against the server's release.
+    #[test]
+    fn test_minimum_version() {
+        assert_eq!("7.5".parse(), Ok(ServerVersion::new(7, 5, 0)));
+        assert!("7.x".parse::<ServerVersion>().is_err());
+        assert!("1.2.3.4".parse::<ServerVersion>().is_err());
+
+        let tidb = ServerInfo::parse("8.0.11-TiDB-v7.5.1").unwrap();
+        assert!(tidb.require_version(ServerVersion::new(7, 5, 0)).is_ok());
+        assert!(tidb.require_version(ServerVersion::new(8, 0, 0)).is_err());
+    }
+
+    /// Test 3: The server version is queried through the connection.
+    #[tokio::test]
+    async fn test_detect_server_info() {
+        let db = MockDatabase::new(DatabaseBackend::MySql)
+            .append_query_results([vec![BTreeMap::from([(
+                "version",
+                Value::from("8.0.11-TiDB-v7.1.0"),
+            )])]])
+            .into_connection();
+
+        let info = detect_server_info(&db).await.unwrap();
+        assert_eq!(info.flavor, ServerFlavor::TiDb);
+        assert_eq!(info.tidb_version, Some(ServerVersion::new(7, 1, 0)));
+    }
+}
@@ -0,0 +1,70 @@
+//! # Graceful Shutdown
+//!
+//! This module defines the report returned by `PoolHandle::shutdown`, and
+//! `shutdown_signal`, a future that completes when the process is asked to
+//! terminate, to tie the shutdown of a pool to the shutdown of the application.
+
+use futures_util::future::{Either, select};
+use std::pin::pin;
+use std::time::Duration;
+use tracing::info;
+
+/// The outcome of `PoolHandle::shutdown`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ShutdownReport {
+    /// The number of connections in use when the shutdown started.
+    pub in_use: u32,
+    /// The number of connections still in use when the deadline passed.
+    ///
+    /// The pool stops waiting for them: each one is closed as soon as it is
+    /// released instead of being returned to the pool.
+    pub force_closed: u32,
+    /// The time the shutdown took.
+    pub elapsed: Duration,
+}
+
+impl ShutdownReport {
+    /// Returns `true` if every connection was returned before the deadline.
+    pub fn is_clean(&self) -> bool {
+        self.force_closed == 0
+    }
+}
+
+/// Completes when the process receives Ctrl+C or, on Unix, `SIGTERM`.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use seaorm_pool::shutdown_signal;
+/// use std::time::Duration;
+///
+/// let report = handle
+///     .clone()
+///     .shutdown_on(shutdown_signal(), Duration::from_secs(30))
+///     .await;
+/// ```
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        if tokio::signal::ctrl_c().await.is_err() {
+            // Without a handler the signal can never be observed.
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(_) => std::future::pending::<()>().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    match select(pin!(ctrl_c), pin!(terminate)).await {
+        Either::Left(_) => info!("Received Ctrl+C, shutting down..."),
+        Either::Right(_) => info!("Received SIGTERM, shutting down..."),
+    }
+}
@@ -0,0 +1,496 @@
+//! # Transaction Helper
+//!
+//! This module provides `run_in_transaction`, which runs a callback inside a
+//! transaction configured by `TransactionOptions`: the isolation level, the
+//! access mode, a time limit and the TiDB transaction mode.
+//!
+//! The transaction is committed if the callback returns `Ok` and rolled back if
+//! it returns `Err`, panics, or exceeds the configured timeout.
+//!
+//! It also provides `run_at_snapshot`, which runs a block of queries against a
+//! TiDB snapshot of the past using a stale read transaction.
+
+use crate::config::{TransactionOptions, quote_literal};
+use futures_util::FutureExt;
+use sea_orm::{ConnectionTrait, DatabaseTransaction, DbErr, TransactionTrait};
+use std::future::Future;
+use std::panic::{AssertUnwindSafe, resume_unwind};
+use std::pin::Pin;
+use std::time::Duration;
+use tracing::{error, warn};
+
+/// The prefix of the error returned when a transaction exceeds its timeout.
+const TIMEOUT_MESSAGE: &str = "transaction timed out after";
+
+/// Runs `callback` in a new transaction configured by `options`.
+///
+/// The transaction is started with the configured isolation level, access
+/// mode and TiDB transaction mode, then:
+///
+/// - if the callback returns `Ok`, the transaction is committed;
+/// - if it returns `Err`, the transaction is rolled back and the error returned;
+/// - if it panics, the transaction is rolled back and the panic resumed;
+/// - if it runs longer than `options.timeout`, it is cancelled, the transaction
+///   is rolled back and a `DbErr::Custom` error is returned.
+///
+/// The timeout is enforced by the client. A statement that is running on the
+/// server when it expires is not killed: the server keeps executing it, and the
+/// rollback only takes effect once it has finished. Set `max_execution_time` in
+/// `DatabaseConfig::session_variables` to also bound `SELECT` statements on the
+/// server.
+///
+/// Use `PoolHandle::run_in_transaction` to apply the defaults configured in
+/// `DatabaseConfig::transaction`.
+///
+/// # Errors
+/// Returns the callback's error, or a `DbErr` (converted into `E`) if the
+/// transaction could not be started, committed, or finished in time.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use seaorm_pool::{TransactionAccessMode, TransactionOptions, run_in_transaction};
+/// use std::time::Duration;
+///
+/// let options = TransactionOptions {
+///     access_mode: Some(TransactionAccessMode::ReadOnly),
+///     timeout: Some(Duration::from_secs(5)),
+///     ..Default::default()
+/// };
+/// let count = run_in_transaction(&pool, &options, |txn| {
+///     Box::pin(async move { Ok::<_, DbErr>(count_orders(txn).await?) })
+/// })
+/// .await?;
+/// ```
+pub async fn run_in_transaction<C, F, T, E>(
+    db: &C,
+    options: &TransactionOptions,
+    callback: F,
+) -> Result<T, E>
+where
+    C: TransactionTrait,
+    F: for<'c> FnOnce(
+            &'c DatabaseTransaction,
+        ) -> Pin<Box<dyn Future<Output = Result<T, E>> + Send + 'c>>
+        + Send,
+    T: Send,
+    E: From<DbErr> + Send,
+{
+    let txn = begin(db, options).await?;
+    finish(txn, options.timeout, callback).await
+}
+
+/// The point in time read by `run_at_snapshot`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SnapshotTimestamp {
+    /// The data as it was this long before the transaction starts.
+    Staleness(Duration),
+    /// The data as it was at an exact timestamp, e.g. `2024-05-01 12:00:00`.
+    Exact(String),
+}
+
+impl SnapshotTimestamp {
+    /// Returns the SQL expression of the timestamp.
+    fn to_sql(&self) -> Result<String, DbErr> {
+        match self {
+            Self::Staleness(staleness) => Ok(format!(
+                "NOW(6) - INTERVAL {} MICROSECOND",
+                staleness.as_micros()
+            )),
+            Self::Exact(timestamp) if timestamp.contains('\\') => Err(DbErr::Custom(format!(
+                "invalid snapshot timestamp `{timestamp}`"
+            ))),
+            Self::Exact(timestamp) => Ok(quote_literal(timestamp)),
+        }
+    }
+}
+
+/// Runs `callback` in a read-only TiDB transaction that reads the data as of
+/// `timestamp`.
+///
+/// The transaction is started with `START TRANSACTION READ ONLY AS OF
+/// TIMESTAMP`, so every query in `callback` sees the same snapshot and may be
+/// served by a follower replica. It is committed or rolled back like in
+/// `run_in_transaction`.
+///
+/// # Errors
+/// Returns the callback's error, or a `DbErr` (converted into `E`) if the
+/// transaction could not be started, for instance because the server is not
+/// TiDB or the timestamp is older than the garbage collection safe point.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use seaorm_pool::{SnapshotTimestamp, run_at_snapshot};
+/// use std::time::Duration;
+///
+/// let snapshot = SnapshotTimestamp::Staleness(Duration::from_secs(10));
+/// let report = run_at_snapshot(&pool, &snapshot, |txn| {
+///     Box::pin(async move { build_report(txn).await })
+/// })
+/// .await?;
+/// ```
+pub async fn run_at_snapshot<C, F, T, E>(
+    db: &C,
+    timestamp: &SnapshotTimestamp,
+    callback: F,
+) -> Result<T, E>
+where
+    C: TransactionTrait,
+    F: for<'c> FnOnce(
+            &'c DatabaseTransaction,
+        ) -> Pin<Box<dyn Future<Output = Result<T, E>> + Send + 'c>>
+        + Send,
+    T: Send,
+    E: From<DbErr> + Send,
+{
+    let timestamp = timestamp.to_sql()?;
+    let txn = begin_with_statements(
+        db,
+        &[format!(
+            "START TRANSACTION READ ONLY AS OF TIMESTAMP {timestamp}"
+        )],
+    )
+    .await?;
+    finish(txn, None, callback).await
+}
+
+/// Runs `callback` in a started transaction, then commits it or rolls it back.
+async fn finish<F, T, E>(
+    txn: DatabaseTransaction,
+    timeout: Option<Duration>,
+    callback: F,
+) -> Result<T, E>
+where
+    F: for<'c> FnOnce(
+            &'c DatabaseTransaction,
+        ) -> Pin<Box<dyn Future<Output = Result<T, E>> + Send + 'c>>
+        + Send,
+    T: Send,
+    E: From<DbErr> + Send,
+{
+    let outcome = {
+        let call = AssertUnwindSafe(callback(&txn)).catch_unwind();
+        match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, call).await.ok(),
+            None => Some(call.await),
+        }
+    };
+
+    match outcome {
+        Some(Ok(Ok(value))) => {
+            txn.commit().await?;
+            Ok(value)
+        }
+        Some(Ok(Err(err))) => {
+            rollback(txn).await;
+            Err(err)
+        }
+        Some(Err(panic)) => {
+            rollback(txn).await;
+            resume_unwind(panic)
+        }
+        None => {
+            rollback(txn).await;
+            let timeout = timeout.unwrap_or_default();
+            warn!("Transaction exceeded its timeout of {:?}", timeout);
+            Err(DbErr::Custom(format!("{TIMEOUT_MESSAGE} {timeout:?}")).into())
+        }
+    }
+}
+
+/// Starts a transaction configured by `options`.
+async fn begin<C>(db: &C, options: &TransactionOptions) -> Result<DatabaseTransaction, DbErr>
+where
+    C: TransactionTrait,
+{
+    options.validate("transaction")?;
+    let (isolation_level, access_mode) = characteristics(options);
+    match options.tidb_txn_mode {
+        None => db.begin_with_config(isolation_level, access_mode).await,
+        Some(mode) => {
+            let mut statements = Vec::new();
+            if let Some(set) = set_transaction(isolation_level, access_mode) {
+                statements.push(set);
+            }
+            statements.push(format!("BEGIN {}", mode.as_str().to_ascii_uppercase()));
+            begin_with_statements(db, &statements).await
+        }
+    }
+}
+
+/// Returns the isolation level and access mode of `options` as `sea-orm`
+/// values, which `begin_with_config` sends as `SET TRANSACTION` before `BEGIN`.
+fn characteristics(
+    options: &TransactionOptions,
+) -> (Option<sea_orm::IsolationLevel>, Option<sea_orm::AccessMode>) {
+    (
+        options.isolation_level.map(Into::into),
+        options.access_mode.map(Into::into),
+    )
+}
+
+/// Returns the `SET TRANSACTION` statement `begin_with_config` would send, for
+/// transactions that `begin_with_statements` starts instead. It only applies
+/// to the next transaction, so the session keeps its defaults.
+fn set_transaction(
+    isolation_level: Option<sea_orm::IsolationLevel>,
+    access_mode: Option<sea_orm::AccessMode>,
+) -> Option<String> {
+    let mut settings = Vec::new();
+    if let Some(level) = isolation_level {
+        settings.push(format!("ISOLATION LEVEL {level}"));
+    }
+    if let Some(mode) = access_mode {
+        settings.push(mode.to_string());
+    }
+    (!settings.is_empty()).then(|| format!("SET TRANSACTION {}", settings.join(", ")))
+}
+
+/// Starts a transaction with custom statements instead of a plain `BEGIN`.
+///
+/// `sea-orm` opens transactions with `BEGIN` and only runs `SET TRANSACTION`
+/// before it, so starting a TiDB mode or a stale read transaction, which need
+/// a statement of their own, is the only place working around it:
+///
+/// 1. a `DatabaseTransaction` is opened with `BEGIN`, which pins a connection;
+/// 2. that empty transaction is ended right away with a raw `COMMIT`;
+/// 3. `statements` run on the same connection and must start a new
+///    transaction, e.g. `BEGIN OPTIMISTIC` or `START TRANSACTION ... AS OF`.
+///
+/// The `DatabaseTransaction` still believes it is open, so its `commit` and
+/// `rollback` end the transaction started by `statements`. If a statement
+/// fails, dropping the `DatabaseTransaction` rolls back whatever was started.
+async fn begin_with_statements<C>(
+    db: &C,
+    statements: &[String],
+) -> Result<DatabaseTransaction, DbErr>
+where
+    C: TransactionTrait,
+{
+    let txn = db.begin().await?;
+    txn.execute_unprepared("COMMIT").await?;
+    for statement in statements {
+        txn.execute_unprepared(statement).await?;
+    }
+    Ok(txn)
+}
+
+/// Returns `true` if the error was returned by `run_in_transaction` because the
+/// transaction exceeded its timeout.
+pub fn is_transaction_timeout_error(err: &DbErr) -> bool {
+    matches!(err, DbErr::Custom(message) if message.starts_with(TIMEOUT_MESSAGE))
+}
+
+/// Rolls back a transaction, logging instead of returning a failure so that
+/// the error that caused the rollback is the one reported.
+async fn rollback(txn: DatabaseTransaction) {
+    if let Err(err) = txn.rollback().await {
+        error!("Failed to roll back transaction: {}", err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{TidbTxnMode, TransactionAccessMode, TransactionIsolation};
+    use sea_orm::{DatabaseBackend, MockDatabase, MockExecResult, Statement};
+    use std::time::Duration;
+
+    fn transaction_log(db: sea_orm::DatabaseConnection) -> Vec<String> {
+        db.into_transaction_log()
+            .into_iter()
+            .flat_map(|txn| txn.statements().to_vec())
+            .map(|stmt| stmt.sql)
+            .collect()
+    }
+
+    /// Test 1: The transaction is started by `begin_with_config` with the
+    /// configured characteristics, in a single transaction, and committed when
+    /// the callback succeeds.
+    #[tokio::test]
+    async fn test_commits_on_ok() {
+        let db = MockDatabase::new(DatabaseBackend::MySql).into_connection();
+        let options = TransactionOptions {
+            isolation_level: Some(TransactionIsolation::ReadCommitted),
+            access_mode: Some(TransactionAccessMode::ReadOnly),
+            ..Default::default()
+        };
+
+        let (isolation_level, access_mode) = characteristics(&options);
+        assert_eq!(
+            set_transaction(isolation_level, access_mode).as_deref(),
+            Some("SET TRANSACTION ISOLATION LEVEL READ COMMITTED, READ ONLY")
+        );
+
+        let result =
+            run_in_transaction(&db, &options, |_txn| Box::pin(async { Ok::<_, DbErr>(42) })).await;
+        assert_eq!(result, Ok(42));
+
+        // The mock connection ignores the characteristics; a MySQL connection
+        // receives the `SET TRANSACTION` above before `BEGIN`.
+        assert_eq!(transaction_log(db), vec!["BEGIN", "COMMIT"]);
+    }
+
+    /// Test 2: The transaction is rolled back when the callback fails.
+    #[tokio::test]
+    async fn test_rolls_back_on_err() {
+        let db = MockDatabase::new(DatabaseBackend::MySql).into_connection();
+
+        let result: Result<(), DbErr> =
+            run_in_transaction(&db, &TransactionOptions::default(), |_txn| {
+                Box::pin(async { Err(DbErr::Custom("failed".to_string())) })
+            })
+            .await;
+        assert_eq!(result, Err(DbErr::Custom("failed".to_string())));
+        assert_eq!(
+            transaction_log(db).last().map(String::as_str),
+            Some("ROLLBACK")
+        );
+    }
+
+    /// Test 3: The transaction is rolled back and the panic resumed when the
+    /// callback panics.
+    #[tokio::test]
+    async fn test_rolls_back_on_panic() {
+        let db = MockDatabase::new(DatabaseBackend::MySql).into_connection();
+
+        let result = AssertUnwindSafe(run_in_transaction(
+            &db,
+            &TransactionOptions::default(),
+            |_txn| -> Pin<Box<dyn Future<Output = Result<(), DbErr>> + Send>> {
+                Box::pin(async { panic!("callback panicked") })
+            },
+        ))
+        .catch_unwind()
+        .await;
+        assert!(result.is_err());
+        assert_eq!(
+            transaction_log(db).last().map(String::as_str),
+            Some("ROLLBACK")
+        );
+    }
+
+    /// Test 4: The transaction is rolled back when it exceeds its timeout.
+    #[tokio::test]
+    async fn test_rolls_back_on_timeout() {
+        let db = MockDatabase::new(DatabaseBackend::MySql).into_connection();
+        let options = TransactionOptions {
+            timeout: Some(Duration::from_millis(10)),
+            ..Default::default()
+        };
+
+        let result: Result<(), DbErr> = run_in_transaction(&db, &options, |txn| {
+            Box::pin(async move {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                txn.execute(Statement::from_string(DatabaseBackend::MySql, "DO 1"))
+                    .await?;
+                Ok(())
+            })
+        })
+        .await;
+        assert!(is_transaction_timeout_error(&result.unwrap_err()));
+        assert_eq!(
+            transaction_log(db).last().map(String::as_str),
+            Some("ROLLBACK")
+        );
+    }
+
+    /// Test 5: A TiDB transaction mode override restarts the transaction with
+    /// `BEGIN <mode>`, keeping the isolation level.
+    #[tokio::test]
+    async fn test_tidb_txn_mode_override() {
+        let db = MockDatabase::new(DatabaseBackend::MySql)
+            .append_exec_results(vec![MockExecResult::default(); 3])
+            .into_connection();
+        let options = TransactionOptions {
+            isolation_level: Some(TransactionIsolation::ReadCommitted),
+            tidb_txn_mode: Some(TidbTxnMode::Optimistic),
+            ..Default::default()
+        };
+
+        let result =
+            run_in_transaction(&db, &options, |_txn| Box::pin(async { Ok::<_, DbErr>(()) })).await;
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            transaction_log(db),
+            vec![
+                "BEGIN",
+                "COMMIT",
+                "SET TRANSACTION ISOLATION LEVEL READ COMMITTED",
+                "BEGIN OPTIMISTIC",
+                "COMMIT"
+            ]
+        );
+    }
+
+    /// Test 6: `run_at_snapshot` reads in a stale read-only transaction.
+    #[tokio::test]
+    async fn test_run_at_snapshot() {
+        let db = MockDatabase::new(DatabaseBackend::MySql)
+            .append_exec_results(vec![MockExecResult::default(); 4])
+            .into_connection();
+
+        let staleness = SnapshotTimestamp::Staleness(Duration::from_secs(5));
+        let result = run_at_snapshot(&db, &staleness, |_txn| {
+            Box::pin(async { Ok::<_, DbErr>(()) })
+        })
+        .await;
+        assert_eq!(result, Ok(()));
+
+        let exact = SnapshotTimestamp::Exact("2024-05-01 12:00:00".to_string());
+        let result =
+            run_at_snapshot(&db, &exact, |_txn| Box::pin(async { Ok::<_, DbErr>(()) })).await;
+        assert_eq!(result, Ok(()));
+
+        let log = transaction_log(db);
+        assert_eq!(
+            log[2],
+            "START TRANSACTION READ ONLY AS OF TIMESTAMP NOW(6) - INTERVAL 5000000 MICROSECOND"
+        );
+        assert_eq!(
+            log[6],
+            "START TRANSACTION READ ONLY AS OF TIMESTAMP '2024-05-01 12:00:00'"
+        );
+    }
+
+    /// Test 7: `begin_with_statements` ends the transaction opened by
+    /// `sea-orm`, then sends the statements, and the returned transaction ends
+    /// the one they started.
+    #[tokio::test]
+    async fn test_begin_with_statements() {
+        let db = MockDatabase::new(DatabaseBackend::MySql)
+            .append_exec_results(vec![MockExecResult::default(); 3])
+            .into_connection();
+
+        let txn = begin_with_statements(&db, &["BEGIN OPTIMISTIC".to_string()])
+            .await
+            .expect("Failed to begin");
+        txn.execute_unprepared("DO 1")
+            .await
+            .expect("Failed to execute");
+        txn.rollback().await.expect("Failed to roll back");
+
+        assert_eq!(
+            transaction_log(db),
+            vec!["BEGIN", "COMMIT", "BEGIN OPTIMISTIC", "DO 1", "ROLLBACK"]
+        );
+    }
+
+    /// Test 8: A TiDB transaction mode override is rejected for a read-only
+    /// transaction instead of being silently dropped.
+    #[tokio::test]
+    async fn test_rejects_tidb_txn_mode_for_read_only() {
+        let db = MockDatabase::new(DatabaseBackend::MySql).into_connection();
+        let options = TransactionOptions {
+            access_mode: Some(TransactionAccessMode::ReadOnly),
+            tidb_txn_mode: Some(TidbTxnMode::Pessimistic),
+            ..Default::default()
+        };
+
+        let result =
+            run_in_transaction(&db, &options, |_txn| Box::pin(async { Ok::<_, DbErr>(()) })).await;
+        assert!(result.is_err());
+        assert!(transaction_log(db).is_empty());
+    }
+}
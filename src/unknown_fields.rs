@@ -0,0 +1,320 @@
+//! # Unknown Field Detection
+//!
+//! Fields that are not part of the configuration are ignored when it is
+//! deserialized, so a typo such as `maxConnection = 50` silently leaves the
+//! default value in place. This module finds such fields in the `database`
+//! table of a parsed configuration file and suggests the known field closest
+//! to each one.
+//!
+//! Tables outside `database` belong to the application and are not checked.
+//! Fields removed in earlier versions, such as `poolOptions.tcpKeepalive`, are
+//! logged with the reason they were removed rather than reported as unknown.
+
+use crate::config::{
+    AppConfig, CircuitBreakerOptions, DatabaseConfig, PoolOptions, PreflightOptions, RetryOptions,
+    TableFamilyOptions, TransactionOptions,
+};
+use crate::error::ConfigError;
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+use serde_ignored::Path;
+use serde_json::Value;
+use tracing::warn;
+
+/// Fields that earlier versions accepted but no longer apply. Configurations
+/// that still set them are logged with a warning instead of being rejected as
+/// unknown fields, so that they keep loading in strict mode.
+const REMOVED_FIELDS: [(&str, &str); 2] = [
+    (
+        "poolOptions.tcpKeepalive",
+        "the MySQL driver does not expose socket options; configure keepalive \
+         probes in the operating system",
+    ),
+    (
+        "poolOptions.tcpNodelay",
+        "the MySQL driver always enables TCP_NODELAY",
+    ),
+];
+
+/// Finds the unknown fields of the `database` table of a parsed configuration
+/// file, returning a `ConfigError::UnknownField` for each one.
+///
+/// # Examples
+///
+/// ```
+/// use seaorm_pool::find_unknown_fields;
+///
+/// let config = serde_json::json!({
+///     "database": { "poolOptions": { "maxConnection": 50 } }
+/// });
+/// let unknown = find_unknown_fields(&config);
+/// assert_eq!(
+///     unknown[0].to_string(),
+///     "unknown field `database.poolOptions.maxConnection`, did you mean `maxConnections`?"
+/// );
+/// ```
+pub fn find_unknown_fields(config: &Value) -> Vec<ConfigError> {
+    let Some(Value::Object(database)) = config.get("database") else {
+        return Vec::new();
+    };
+    let mut database = database.clone();
+    database.remove("url");
+
+    let mut unknown = Vec::new();
+    // Values that fail to deserialize are reported when the configuration is
+    // loaded, so only the fields found before the failure matter here.
+    let _ = serde_ignored::deserialize(Value::Object(database), |path| {
+        let mut segments = Vec::new();
+        collect_segments(&path, &mut segments);
+        let path = segments.join(".");
+        if let Some((_, reason)) = REMOVED_FIELDS.iter().find(|(field, _)| *field == path) {
+            warn!("Ignoring removed field `database.{path}`: {reason}");
+            return;
+        }
+        let (field, parent) = segments.split_last().expect("ignored fields have a path");
+        let suggestion = known_fields(parent).and_then(|known| suggest(field, known));
+        unknown.push(ConfigError::unknown_field(
+            format!("database.{path}"),
+            suggestion,
+        ));
+    }) as Result<DatabaseConfig, _>;
+    unknown
+}
+
+impl AppConfig {
+    /// Parses an `AppConfig` from TOML, rejecting unknown fields in the
+    /// `database` table.
+    ///
+    /// This is the strict counterpart of `toml::from_str::<AppConfig>`, which
+    /// silently ignores a typo such as `maxConnection`.
+    ///
+    /// # Errors
+    /// Returns a `ConfigError::UnknownField` for the first unknown field, with
+    /// the closest known field as a suggestion, or a `ConfigError::Parse` if
+    /// the TOML is malformed or does not form an `AppConfig`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use seaorm_pool::AppConfig;
+    ///
+    /// let err = AppConfig::from_toml_strict(
+    ///     "[database]\nusername = \"app\"\npassword = \"secret\"\ndatabaseName = \"app\"\n\
+    ///      [database.poolOptions]\nmaxConnection = 50\n",
+    /// )
+    /// .unwrap_err();
+    /// assert_eq!(
+    ///     err.to_string(),
+    ///     "unknown field `database.poolOptions.maxConnection`, did you mean `maxConnections`?"
+    /// );
+    /// ```
+    pub fn from_toml_strict(contents: &str) -> Result<Self, ConfigError> {
+        let parse_error = |err: toml::de::Error| ConfigError::parse("TOML", err.to_string());
+        let value: Value = toml::from_str(contents).map_err(parse_error)?;
+        if let Some(err) = find_unknown_fields(&value).into_iter().next() {
+            return Err(err);
+        }
+        toml::from_str(contents).map_err(parse_error)
+    }
+}
+
+/// Collects the keys and indices of a path, skipping `Option` and newtype
+/// wrappers.
+fn collect_segments(path: &Path<'_>, segments: &mut Vec<String>) {
+    match path {
+        Path::Root => {}
+        Path::Seq { parent, index } => {
+            collect_segments(parent, segments);
+            segments.push(index.to_string());
+        }
+        Path::Map { parent, key } => {
+            collect_segments(parent, segments);
+            segments.push(key.clone());
+        }
+        Path::Some { parent }
+        | Path::NewtypeStruct { parent }
+        | Path::NewtypeVariant { parent } => collect_segments(parent, segments),
+    }
+}
+
+/// Returns the fields of the structure found at `parent`, a path within the
+/// `database` table.
+fn known_fields(parent: &[String]) -> Option<&'static [&'static str]> {
+    let parent = parent.iter().map(String::as_str).collect::<Vec<_>>();
+    let fields = match parent.as_slice() {
+        [] => field_names::<DatabaseConfig>(),
+        ["poolOptions"] => field_names::<PoolOptions>(),
+        ["poolOptions", "retry"] => field_names::<RetryOptions>(),
+        ["poolOptions", "circuitBreaker"] => field_names::<CircuitBreakerOptions>(),
+        ["transaction"] => field_names::<TransactionOptions>(),
+        ["preflight"] => field_names::<PreflightOptions>(),
+        ["tableFamilies", _] => field_names::<TableFamilyOptions>(),
+        _ => return None,
+    };
+    Some(fields)
+}
+
+/// Returns the known field closest to `field`: one written in another case
+/// (e.g. `max_connections`), or one within a few typos of it.
+fn suggest(field: &str, known: &[&str]) -> Option<String> {
+    let normalize = |name: &str| name.replace(['_', '-'], "").to_ascii_lowercase();
+    if let Some(name) = known
+        .iter()
+        .find(|name| normalize(name) == normalize(field))
+    {
+        return Some(name.to_string());
+    }
+    let max_distance = (field.len() / 3).clamp(1, 3);
+    known
+        .iter()
+        .map(|name| (strsim::damerau_levenshtein(field, name), name))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, name)| name.to_string())
+}
+
+/// Returns the field names of a structure deriving `Deserialize`, as declared
+/// to serde.
+fn field_names<'de, T: Deserialize<'de>>() -> &'static [&'static str] {
+    /// A deserializer that records the fields requested by a structure and
+    /// then fails.
+    struct FieldNames<'a>(&'a mut &'static [&'static str]);
+
+    impl<'de> Deserializer<'de> for FieldNames<'_> {
+        type Error = de::value::Error;
+
+        fn deserialize_any<V: Visitor<'de>>(self, _: V) -> Result<V::Value, Self::Error> {
+            Err(de::Error::custom("not a structure"))
+        }
+
+        fn deserialize_struct<V: Visitor<'de>>(
+            self,
+            _: &'static str,
+            fields: &'static [&'static str],
+            _: V,
+        ) -> Result<V::Value, Self::Error> {
+            *self.0 = fields;
+            Err(de::Error::custom("field names recorded"))
+        }
+
+        forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+            bytes byte_buf option unit unit_struct newtype_struct seq tuple
+            tuple_struct map enum identifier ignored_any
+        }
+    }
+
+    let mut fields: &'static [&'static str] = &[];
+    let _ = T::deserialize(FieldNames(&mut fields));
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Test 1: Typos and snake_case names are reported with suggestions.
+    #[test]
+    fn test_unknown_fields_with_suggestions() {
+        let config = json!({
+            "server": { "port": 8080 },
+            "database": {
+                "url": "mysql://app@localhost/app",
+                "hots": "db.internal",
+                "poolOptions": {
+                    "max_connections": 50,
+                    "circuitBreaker": { "openDuraton": "30s" }
+                },
+                "sessionVariables": { "time_zone": "+00:00" },
+                "tableFamilies": { "analytics": { "resourceGrup": "olap" } },
+                "completelyUnrelated": true
+            }
+        });
+        let mut unknown = find_unknown_fields(&config)
+            .into_iter()
+            .map(|err| match err {
+                ConfigError::UnknownField { field, suggestion } => (field, suggestion),
+                err => panic!("unexpected error: {err}"),
+            })
+            .collect::<Vec<_>>();
+        let expected = [
+            ("database.completelyUnrelated", None),
+            ("database.hots", Some("host")),
+            (
+                "database.poolOptions.circuitBreaker.openDuraton",
+                Some("openDuration"),
+            ),
+            (
+                "database.poolOptions.max_connections",
+                Some("maxConnections"),
+            ),
+            (
+                "database.tableFamilies.analytics.resourceGrup",
+                Some("resourceGroup"),
+            ),
+        ];
+        unknown.sort();
+        assert_eq!(
+            unknown,
+            expected.map(|(field, suggestion)| (field.to_string(), suggestion.map(str::to_string)))
+        );
+    }
+
+    /// Test 2: A configuration with known fields only is accepted.
+    #[test]
+    fn test_known_fields_accepted() {
+        let config = serde_json::to_value(crate::config::AppConfig {
+            database: DatabaseConfig::default(),
+        })
+        .unwrap();
+        assert!(find_unknown_fields(&config).is_empty());
+        assert!(field_names::<PoolOptions>().contains(&"maxConnections"));
+    }
+
+    /// Test 3: Strict TOML parsing accepts known fields and reports typos and
+    /// malformed files.
+    #[test]
+    fn test_from_toml_strict() {
+        let valid = "[database]\nusername = \"app\"\npassword = \"secret\"\n\
+                     databaseName = \"app\"\n\n[logging]\nlevel = \"debug\"\n";
+        let config = AppConfig::from_toml_strict(valid).unwrap();
+        assert_eq!(config.database.username, "app");
+
+        let typo = format!("{valid}\n[database.transaction]\nisolationLvel = \"serializable\"\n");
+        assert_eq!(
+            AppConfig::from_toml_strict(&typo).unwrap_err(),
+            ConfigError::unknown_field(
+                "database.transaction.isolationLvel",
+                Some("isolationLevel".to_string())
+            )
+        );
+
+        let malformed = "[database]\nusername = ";
+        assert!(matches!(
+            AppConfig::from_toml_strict(malformed),
+            Err(ConfigError::Parse { .. })
+        ));
+    }
+
+    /// Test 4: Removed TCP options are ignored with a warning rather than
+    /// reported as unknown, so old configurations still load strictly.
+    #[test]
+    fn test_removed_fields_are_not_unknown() {
+        let config = json!({
+            "database": {
+                "poolOptions": { "tcpKeepalive": "60s", "tcpNodelay": true, "tcpNodelai": true }
+            }
+        });
+        assert_eq!(
+            find_unknown_fields(&config),
+            [ConfigError::unknown_field(
+                "database.poolOptions.tcpNodelai",
+                None
+            )]
+        );
+
+        let old = "[database]\nusername = \"app\"\npassword = \"secret\"\n\
+                   databaseName = \"app\"\n\n[database.poolOptions]\ntcpNodelay = true\n";
+        assert!(AppConfig::from_toml_strict(old).is_ok());
+    }
+}